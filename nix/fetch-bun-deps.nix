@@ -16,6 +16,19 @@ let
     bun2nix -o bun.nix
     ```
   '';
+
+  supportedSchemaVersion = 1;
+
+  staleBunNixErr = ''
+    Your supplied `bun.nix` dependencies file was not generated from
+    the supplied `bun.lock` file, so it is likely out of date.
+
+    Try regenerating your `bun.nix` file after any changes to `bun.lock`:
+
+    ```sh
+    bun2nix -o bun.nix
+    ```
+  '';
 in
 {
   options.perSystem = mkPerSystemOption {
@@ -38,20 +51,43 @@ in
       fetchBunDeps.function =
         {
           bunNix,
+          bunLock ? null,
+          includeDevPackages ? false,
           overrides ? { },
           ...
         }@args:
         let
           attrIsBunPkg = _: value: lib.isStorePath value;
 
-          withErrCtx = builtins.addErrorContext invalidBunNixErr (pkgs.callPackage bunNix { });
+          bunNixFunction =
+            if lib.hasSuffix ".json" (toString bunNix) then config.fetchBunDeps.fromJSON bunNix else bunNix;
+
+          withErrCtx = builtins.addErrorContext invalidBunNixErr (pkgs.callPackage bunNixFunction { });
+
+          # Packages only needed by dev dependencies, from a `bun.nix`
+          # generated with `--production --dev-packages`
+          devPackages = lib.optionalAttrs includeDevPackages (withErrCtx.devPackages or { });
 
-          packages = lib.filterAttrs attrIsBunPkg withErrCtx;
+          packages = lib.filterAttrs attrIsBunPkg withErrCtx // devPackages;
 
-          buildPackage = config.fetchBunDeps.buildPackage args;
+          provenance = withErrCtx._bun2nix or null;
+
+          buildPackage = config.fetchBunDeps.buildPackage (
+            args // { bunNixMetadata = withErrCtx._metadata or { }; }
+          );
           overridePackage = config.fetchBunDeps.overridePackage args;
         in
 
+        assert lib.assertMsg (
+          provenance == null || provenance.schemaVersion == supportedSchemaVersion
+        ) invalidBunNixErr;
+
+        assert lib.assertMsg (
+          bunLock == null
+          || provenance == null
+          || provenance.lockfileSha256 == builtins.hashFile "sha256" bunLock
+        ) staleBunNixErr;
+
         assert lib.asserts.assertEachOneOf "overrides" (builtins.attrNames overrides) (
           builtins.attrNames packages
         );
@@ -13,6 +13,10 @@ in
         If `patchShebangs` is enabled patch all
         scripts to use bun as their executor.
 
+        `patchShebangs` and `autoPatchElf` may also be functions,
+        taking the package's metadata from a `bun.nix` generated
+        with `--metadata` and returning whether to patch it.
+
         Then, produce a bun cache compatible symlink in $out/share/bun-cache.
       '';
       type = types.functionTo (types.functionTo (types.functionTo types.package));
@@ -32,19 +36,28 @@ in
           patchShebangs ? true,
           autoPatchElf ? false,
           nativeBuildInputs ? [ ],
+          bunNixMetadata ? { },
           ...
         }@args:
         let
           bunWithNode = config.fetchBunDeps.bunWithNode args;
         in
         name: pkg:
+        let
+          metadata = bunNixMetadata.${name} or { };
+
+          enabledFor = option: if builtins.isFunction option then option metadata else option;
+
+          shouldPatchShebangs = enabledFor patchShebangs;
+          shouldAutoPatchElf = enabledFor autoPatchElf;
+        in
         pkgs.stdenv.mkDerivation {
           name = "bun-pkg-${name}";
 
           nativeBuildInputs = [
             bunWithNode
           ]
-          ++ lib.optionals autoPatchElf (
+          ++ lib.optionals shouldAutoPatchElf (
             with pkgs;
             [
               autoPatchelfHook
@@ -72,8 +85,8 @@ in
           patchPhase = ''
             runHook prePatch
 
-            ${lib.optionalString patchShebangs ''patchShebangs "$out/share/bun-packages"''}
-            ${lib.optionalString autoPatchElf ''runHook autoPatchelfPostFixup''}
+            ${lib.optionalString shouldPatchShebangs ''patchShebangs "$out/share/bun-packages"''}
+            ${lib.optionalString shouldAutoPatchElf ''runHook autoPatchelfPostFixup''}
 
             runHook postPatch
           '';
@@ -0,0 +1,87 @@
+{ lib, flake-parts-lib, ... }:
+let
+  inherit (flake-parts-lib) mkPerSystemOption;
+  inherit (lib) mkOption types;
+in
+{
+  options.perSystem = mkPerSystemOption {
+    options.fetchBunDeps.fromJSON = mkOption {
+      description = ''
+        Read a `bun.json` file, as created by `bun2nix --format json`,
+        into the same function a `bun.nix` file contains, ready to
+        be called with `pkgs.callPackage`.
+
+        Workspace and file package paths are resolved relative
+        to the directory containing the JSON file.
+      '';
+      type = types.functionTo types.raw;
+    };
+  };
+
+  config.perSystem = {
+    fetchBunDeps.fromJSON =
+      bunJson:
+      let
+        output = lib.importJSON bunJson;
+        root = dirOf bunJson;
+      in
+
+      assert lib.assertMsg (output.schemaVersion == 1) ''
+        Unsupported `bun.json` schema version: ${toString output.schemaVersion}.
+
+        Try regenerating it with the same version of `bun2nix` as your flake input:
+
+        ```sh
+        bun2nix --format json -o bun.json
+        ```
+      '';
+
+      {
+        copyPathToStore,
+        fetchFromGitHub,
+        fetchgit,
+        fetchurl,
+        ...
+      }:
+      let
+        hashOrFake = hash: if hash == null then lib.fakeHash else hash;
+
+        fetch =
+          fetcher:
+          {
+            npm = fetchurl { inherit (fetcher) url hash; };
+            git = fetchgit {
+              inherit (fetcher) url rev;
+              hash = hashOrFake fetcher.hash;
+            };
+            github = fetchFromGitHub {
+              inherit (fetcher) owner repo rev;
+              hash = hashOrFake fetcher.hash;
+            };
+            tarball = builtins.fetchTarball {
+              inherit (fetcher) url;
+              sha256 = hashOrFake fetcher.hash;
+            };
+            path = copyPathToStore (root + "/${fetcher.path}");
+          }
+          .${fetcher.type};
+
+        fetchAll =
+          packages:
+          lib.listToAttrs (map (pkg: lib.nameValuePair pkg.name (fetch pkg.fetcher)) packages);
+
+        devPackages = output.devPackages or [ ];
+
+        allPackages = output.packages ++ devPackages;
+      in
+      fetchAll output.packages
+      // {
+        _metadata = lib.listToAttrs (
+          map (pkg: lib.nameValuePair pkg.name (pkg.metadata or { })) allPackages
+        );
+      }
+      // lib.optionalAttrs (devPackages != [ ]) { devPackages = fetchAll devPackages; }
+      // lib.optionalAttrs (output ? workspaces) { inherit (output) workspaces; }
+      // lib.optionalAttrs (output ? bun2nix) { _bun2nix = output.bun2nix; };
+  };
+}
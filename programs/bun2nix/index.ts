@@ -1,6 +1,13 @@
 #!/usr/bin/env bun
 
-import { convert_lockfile_to_nix_expression, Options } from "./bun2nix-wasm.js";
+import {
+  check_output,
+  Config,
+  convert_lockfile_to_nix_expression,
+  convert_lockfile_to_nix_expression_with_prefetch,
+  Options,
+  OutputFormat,
+} from "./bun2nix-wasm.js";
 
 import sade from "sade";
 import pkgJson from "./package.json" with { type: "json" };
@@ -10,29 +17,195 @@ const prog = sade("bun2nix", true);
 /** `bun2nix` command line options. */
 type CliOpts = {
   /** String path to the lockfile to read in. */
-  "lock-file": string;
+  "lock-file": string | undefined;
   /** Output file to write to - writes to stdout if undefined. */
   "output-file": string | undefined;
   /** The prefix to use when copying workspace or file packages. */
-  "copy-prefix": string;
+  "copy-prefix": string | undefined;
+  /** The format to write the output in, `nix` or `json`. */
+  format: string | undefined;
+  /** Render npm packages as a single line each, through a shared helper. */
+  compact: boolean | undefined;
+  /** Emit the lockfile metadata of every package. */
+  metadata: boolean | undefined;
+  /** Only emit the packages installed for a single workspace, given by it's path or name. */
+  workspace: string | undefined;
+  /** Only emit the packages needed by the dependencies of workspaces. */
+  production: boolean | undefined;
+  /** Emit the packages only needed by dev dependencies in a separate attribute. */
+  "dev-packages": boolean | undefined;
+  /** A JSON file of sources to fetch packages from in place of those in the lockfile. */
+  "fetcher-overrides": string | undefined;
+  /** A template to render the Nix expression with, in place of the built in one. */
+  template: string | undefined;
+  /** Check the existing output file is up to date, rather than writing it. */
+  check: boolean;
+  /** Fail with a list of every source whose hash is not already known. */
+  offline: boolean | undefined;
+  /** Emit `lib.fakeHash` for every source whose hash is not already known. */
+  "placeholder-hashes": boolean | undefined;
 };
 
+/**
+ * Read the project config from `bun2nix.toml`, or failing that
+ * from the `"bun2nix"` key of `package.json`, in the current directory
+ *
+ * @return {Promise<Config>} The project config, which is empty if there is none
+ */
+export async function readConfig(): Promise<Config> {
+  const toml = Bun.file("bun2nix.toml");
+  if (await toml.exists()) {
+    return Config.from_toml(await toml.text());
+  }
+
+  const pkg = Bun.file("package.json");
+  if (await pkg.exists()) {
+    return Config.from_package_json(await pkg.text());
+  }
+
+  return new Config();
+}
+
 /**
  * Generate a nix expression for a given bun lockfile
  * Writes to stdout if `output-file` is not specified.
  *
+ * Flags given on the command line take precedence over the project config.
+ *
  * @param {CliOpts} opts - An instance of bun2nix CLI options
  */
 export async function generateNixExpression(opts: CliOpts): Promise<void> {
-  const lock_file = Bun.file(opts["lock-file"]);
+  const config = await readConfig();
+  const output_path = opts["output-file"] ?? config.output_file;
+
+  const lock_file = Bun.file(opts["lock-file"] ?? config.lock_file ?? "bun.lock");
   const contents = await lock_file.text();
 
-  const options = new Options(opts["copy-prefix"]);
+  const options = config.options;
+  if (opts["copy-prefix"] !== undefined) {
+    options.copy_prefix = opts["copy-prefix"];
+  }
+  if (opts.format !== undefined) {
+    options.format = opts.format === "json" ? OutputFormat.Json : OutputFormat.Nix;
+  }
+  // Switches are unset by their `--no-` form, keeping
+  // the value from the config if neither is given
+  options.compact = opts.compact ?? options.compact;
+  options.metadata = opts.metadata ?? options.metadata;
+  options.workspace = opts.workspace ?? options.workspace;
+  options.production = opts.production ?? options.production;
+  options.dev_packages = opts["dev-packages"] ?? options.dev_packages;
+
+  if (opts["fetcher-overrides"]) {
+    const overrides = await Bun.file(opts["fetcher-overrides"]).json();
+
+    for (const [name, fetcher] of Object.entries(overrides)) {
+      options.add_fetcher_override(name, JSON.stringify(fetcher));
+    }
+  }
+
+  const template = opts.template ?? config.template;
+  if (template) {
+    options.template = await Bun.file(template).text();
+  }
+
+  // Offline mode and placeholder hashes conflict,
+  // so either flag turns the other off in the config
+  options.offline = opts.offline ?? options.offline;
+  options.placeholder_hashes = opts["placeholder-hashes"] ?? options.placeholder_hashes;
+  if (opts.offline) {
+    options.placeholder_hashes = false;
+  }
+  if (opts["placeholder-hashes"]) {
+    options.offline = false;
+  }
+
+  // Checking never prefetches, as any source whose hash isn't
+  // already known means the existing output is out of date
+  options.offline ||= opts.check && !options.placeholder_hashes;
+
+  if (output_path && (await Bun.file(output_path).exists())) {
+    options.existing_output = await Bun.file(output_path).text();
+  }
+
+  if (opts.check && !output_path) {
+    throw new Error("`--check` requires an `--output-file` to compare against");
+  }
+
+  const nix_expression =
+    Bun.which("nix") && !options.offline
+      ? await convert_lockfile_to_nix_expression_with_prefetch(
+          contents,
+          options,
+          prefetchWithNix,
+        )
+      : convertLockfileToNixExpression(contents, options);
+
+  if (opts.check) {
+    const diff = check_output(
+      output_path!,
+      options.existing_output ?? "",
+      nix_expression,
+    );
+
+    if (diff) {
+      process.stdout.write(diff);
+      console.error(
+        `\`${output_path}\` is out of date with the lockfile, regenerate it by running \`bun2nix\` again without \`--check\``,
+      );
+      process.exit(1);
+    }
+
+    return;
+  }
+
+  const output_file = output_path || Bun.stdout;
+  await Bun.write(output_file, nix_expression);
+}
+
+/**
+ * Prefetch a source with `nix flake prefetch`
+ *
+ * @param {string} url - The url of the source, as written in the lockfile
+ * @param {string} kind - The kind of source, `git`, `github` or `tarball`
+ * @return {Promise<string>} The SRI hash of the source
+ */
+export async function prefetchWithNix(
+  url: string,
+  kind: string,
+): Promise<string> {
+  const [source, rev] = url.split("#");
+  const flakeRef =
+    kind === "git"
+      ? `${source}?rev=${rev}`
+      : kind === "github"
+        ? `${source}?ref=${rev}`
+        : url;
+
+  const proc = Bun.spawn(
+    [
+      "nix",
+      "--extra-experimental-features",
+      "nix-command flakes",
+      "flake",
+      "prefetch",
+      flakeRef,
+      "--json",
+    ],
+    { stderr: "pipe" },
+  );
 
-  const nix_expression = convertLockfileToNixExpression(contents, options);
+  const [stdout, stderr, exitCode] = await Promise.all([
+    new Response(proc.stdout).text(),
+    new Response(proc.stderr).text(),
+    proc.exited,
+  ]);
 
-  const output_file = opts["output-file"] || Bun.stdout;
-  await Bun.write(output_file, nix_expression + "\n");
+  if (exitCode !== 0) {
+    throw new Error(`\`nix flake prefetch ${flakeRef}\` failed: ${stderr}`);
+  }
+
+  return JSON.parse(stdout).hash;
 }
 
 prog
@@ -40,8 +213,7 @@ prog
   .describe("Convert Bun (v1.2+) packages to Nix expressions")
   .option(
     "-l, --lock-file",
-    "The Bun (v1.2+) lockfile to use to produce the Nix expression",
-    "bun.lock",
+    "The Bun (v1.2+) lockfile to use to produce the Nix expression (default bun.lock)",
   )
   .option(
     "-o, --output-file",
@@ -49,8 +221,49 @@ prog
   )
   .option(
     "-c, --copy-prefix",
-    "The prefix to use when copying workspace or file packages",
-    "./",
+    "The prefix to use when copying workspace or file packages (default ./)",
+  )
+  .option("-f, --format", "The format to write the output in, `nix` or `json` (default nix)")
+  .option(
+    "--compact",
+    "Render npm packages as a single line each, through a shared helper function, to keep the output small",
+  )
+  .option(
+    "--metadata",
+    "Emit the lockfile metadata of every package, such as it's version, executables and platform constraints",
+  )
+  .option(
+    "-w, --workspace",
+    "Only emit the packages installed for this workspace, given by it's path or name, alongside the workspace packages it depends on",
+  )
+  .option(
+    "--production",
+    "Only emit the packages needed by the dependencies of workspaces, leaving out those only needed by their dev dependencies",
+  )
+  .option(
+    "--dev-packages",
+    "Emit the packages only needed by dev dependencies in a separate `devPackages` attribute, rather than leaving them out",
+  )
+  .option(
+    "--fetcher-overrides",
+    "A JSON file of sources to fetch packages from in place of those in the lockfile, keyed by the package's identifier, such as `zod@3.24.2`",
+  )
+  .option(
+    "-t, --template",
+    "A template to render the Nix expression with, in place of the built in one, written in jinja syntax",
+  )
+  .option(
+    "--check",
+    "Check the existing output file is up to date, rather than writing it, printing a diff and failing if it is not",
+    false,
+  )
+  .option(
+    "--offline",
+    "Never prefetch sources, instead failing with a list of every source whose hash is not already known",
+  )
+  .option(
+    "--placeholder-hashes",
+    "Emit `lib.fakeHash` placeholders for every source whose hash is not already known, instead of failing",
   )
   .action((opts) => generateNixExpression(opts));
 
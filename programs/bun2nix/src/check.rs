@@ -0,0 +1,36 @@
+//! This module handles checking whether a previously generated output is up to date
+
+use similar::TextDiff;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// # Check Output
+///
+/// Compare a previously generated output against a freshly rendered one,
+/// producing a unified diff from the former to the latter if they differ
+///
+/// ## Usage
+///```rust
+/// use bun2nix::check_output;
+///
+/// assert_eq!(check_output("bun.nix", "{ }\n", "{ }\n"), None);
+///
+/// let diff = check_output("bun.nix", "{ }\n", "{\n  a = 1;\n}\n").unwrap();
+///
+/// assert!(diff.starts_with("--- a/bun.nix\n+++ b/bun.nix\n"));
+/// assert!(diff.contains("-{ }\n+{\n+  a = 1;\n+}\n"));
+/// ```
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn check_output(path: &str, existing: &str, rendered: &str) -> Option<String> {
+    if existing == rendered {
+        return None;
+    }
+
+    Some(
+        TextDiff::from_lines(existing, rendered)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string(),
+    )
+}
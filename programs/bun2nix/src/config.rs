@@ -0,0 +1,112 @@
+//! This module holds the project configuration file, which sets the same
+//! options as the command line, so they needn't be repeated on every run
+use serde::Deserialize;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{error::Result, options::Options};
+
+/// # Project Config
+///
+/// Configuration read from a `bun2nix.toml` file, or the `"bun2nix"` key of
+/// a `package.json`, whose keys are named after the command line flags.
+///
+/// Any flags given on the command line take precedence over the config.
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{Config, OutputFormat};
+///
+/// let config = Config::from_toml(r#"
+/// output-file = "bun.nix"
+/// format = "json"
+/// production = true
+///
+/// [[url-rewrites]]
+/// prefix = "https://registry.npmjs.org"
+/// replacement = "https://mirror.example.com/npm"
+/// "#)
+/// .unwrap();
+///
+/// assert_eq!(config.output_file.as_deref(), Some("bun.nix"));
+/// assert_eq!(config.options.format, OutputFormat::Json);
+/// assert!(config.options.production);
+/// assert_eq!(config.options.url_rewrites.len(), 1);
+///
+/// let config = Config::from_package_json(r#"
+/// {
+///   "name": "app",
+///   "bun2nix": { "copy-prefix": "../", "compact": true }
+/// }
+/// "#)
+/// .unwrap();
+///
+/// assert_eq!(config.options.copy_prefix, "../");
+/// assert!(config.options.compact);
+///
+/// // Misspelled keys are rejected, rather than silently ignored
+/// assert!(Config::from_toml("prefetch_timeout = 10").is_err());
+/// ```
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Debug, Default, Clone, Deserialize)]
+// Unknown keys are still rejected with the options flattened in, as the
+// options take every key they know of before the rest are checked
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The Bun (v1.2+) lockfile to read
+    pub lock_file: Option<String>,
+
+    /// The output file to write to
+    pub output_file: Option<String>,
+
+    /// The directory to write split output to, which
+    /// is ignored if `output_file` is also set
+    pub output_dir: Option<String>,
+
+    /// The path of a template to render the nix expression with
+    pub template: Option<String>,
+
+    /// The options to generate the output with
+    #[serde(flatten)]
+    pub options: Options,
+}
+
+/// The keys of a `package.json` which may hold the config
+#[derive(Deserialize)]
+struct PackageJson {
+    #[serde(default)]
+    bun2nix: Config,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Config {
+    /// # New Config
+    ///
+    /// An empty config, which leaves every option at it's default
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Config From TOML
+    ///
+    /// Read the config from the contents of a `bun2nix.toml` file
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// # Config From package.json
+    ///
+    /// Read the config from the `"bun2nix"` key of the contents of
+    /// a `package.json` file, or an empty config if it has none
+    pub fn from_package_json(contents: &str) -> Result<Self> {
+        Ok(serde_json::from_str::<PackageJson>(contents)?.bun2nix)
+    }
+}
+
+impl Config {
+    /// The name of the config file, which is read from
+    /// the project root in preference to `package.json`
+    pub const FILE_NAME: &str = "bun2nix.toml";
+}
@@ -4,9 +4,11 @@
 //! - A giant unified error type `Error`
 //! - An alias for `std::result::Result<T, E>` with that error for convenience
 
-use std::{io, str::Utf8Error};
+use std::{io, process::ExitStatus, str::Utf8Error, time::Duration};
 use thiserror::Error;
 
+use crate::prefetch::UnhashedSource;
+
 /// Result alias for Errors which occur in `bun2nix`
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -31,6 +33,32 @@ Please make sure your bun lockfile is formatted correctly, try deleting it and r
 Make sure all versions in your bun lockfile are formatted properly or try deleting it and running `bun install` to produce a fresh one"
     )]
     NoAtInPackageIdentifier,
+    #[error(
+        "Unsupported JSON output schema version: '{0}'.
+
+Try regenerating the file with this version of `bun2nix`"
+    )]
+    UnsupportedJsonSchemaVersion(u32),
+    #[error("Failed to parse the `bun2nix.toml` config file: \n{0}")]
+    ParseConfig(#[from] toml::de::Error),
+    #[error("Output can only be split into a directory of files in the `nix` format")]
+    UnsupportedSplitOutputFormat,
+    #[error(
+        "`{0}` is out of date with the lockfile.
+
+Regenerate it by running `bun2nix` again without `--check`"
+    )]
+    OutputOutOfDate(String),
+    #[error("No package named `{0}` was found in the lockfile")]
+    PackageNotFound(String),
+    #[error("No workspace with the path or name `{0}` was found in the lockfile")]
+    WorkspaceNotFound(String),
+    #[error("Failed to read `{path}` at the git revision `{revision}`: \n{stderr}")]
+    ReadGitRevisionFailed {
+        path: String,
+        revision: String,
+        stderr: String,
+    },
     #[error( "Unsupported lockfile version: '{0}'.
 
 Consider updating your local package or contributing to `bun2nix` if this version hasn't been supported yet"
@@ -40,12 +68,87 @@ Consider updating your local package or contributing to `bun2nix` if this versio
     FetchingFailed(io::Error),
     #[error("\nConsole error while fetching package from it's source: \n\n{0}")]
     FetchingError(String),
+    #[error("\nPrefetch command `{command}` failed with {status}: \n\n{stderr}")]
+    PrefetchCommandFailed {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+    #[error("\nPrefetch command `{command}` was killed after running for longer than {}s", .timeout.as_secs())]
+    PrefetchTimedOut { command: String, timeout: Duration },
+    #[error("Failed to prefetch `{name}` from `{url}` after {attempts} attempt(s): \n{source}")]
+    PrefetchFailed {
+        name: String,
+        url: String,
+        attempts: u32,
+        source: Box<Error>,
+    },
+    #[error("The prefetch callback failed to hash `{name}` from `{url}`: \n{message}")]
+    PrefetchCallbackFailed {
+        name: String,
+        url: String,
+        message: String,
+    },
+    #[error(
+        "The `{0}` prefetcher does not support hashing this kind of source: \n{1}
+
+Try selecting a different prefetch backend with `--prefetcher`"
+    )]
+    UnsupportedPrefetchSource(&'static str, String),
+    #[error("No fixture hash was provided for the source `{0}`")]
+    MissingFixtureHash(String),
+    #[error("Failed to parse `{0}` as a nix32 encoded sha256 hash")]
+    InvalidNixHash(String),
+    #[error("A downloaded archive contained an invalid path: `{0}`")]
+    InvalidArchivePath(String),
+    #[error(
+        "The source contains the path `{0}`, which is not valid utf8 and cannot be hashed by the builtin prefetcher.
+
+Try selecting a different prefetch backend with `--prefetcher`"
+    )]
+    NonUtf8Path(String),
+    #[error(
+        "A downloaded archive did not contain exactly one top level directory, so it cannot be unpacked as nix would"
+    )]
+    UnexpectedArchiveLayout,
+    #[error(
+        "The git source contains the submodule `{0}`, which the builtin prefetcher cannot hash.
+
+Try selecting a different prefetch backend with `--prefetcher`"
+    )]
+    UnsupportedGitSubmodule(String),
+    #[error(
+        "The git source contains `{0}`, which sets attributes the builtin prefetcher cannot apply.
+
+Try selecting a different prefetch backend with `--prefetcher`"
+    )]
+    UnsupportedGitAttributes(String),
+    #[error("Running in offline mode, but the following sources have no known hash:
+
+{}
+
+Either run these commands to prefetch them, or run `bun2nix` without offline mode", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    OfflineUnhashedSources(Vec<UnhashedSource>),
     #[error("An invalid utf8 string was returned from stdin while fetching a package: {0}")]
     InvalidUtf8String(Utf8Error),
     #[error("A workspace package was missing the `workspace:` specifier")]
     MissingWorkspaceSpecifier,
     #[error("A file package was missing the `file:` specifier")]
     MissingFileSpecifier,
+    #[error(
+        "The workspace or file package path `{0}` points outside of the project root.
+
+Nix can only copy paths inside of the project into the store, so try moving this package into the project"
+    )]
+    PathOutsideProjectRoot(String),
+    #[error(
+        "Fetcher overrides were given for packages which aren't in the lockfile: {}
+
+Make sure each override is keyed by the package's identifier, such as `zod@3.24.2`", .0.join(", ")
+    )]
+    UnknownFetcherOverrides(Vec<String>),
+    #[error("Failed to parse a url rewrite rule's pattern: \n{0}")]
+    InvalidUrlRewrite(regex::Error),
     #[error("A git url was missing it's ref")]
     MissingGitRef,
     #[error("A github url was formatted incorrectly")]
@@ -54,6 +157,8 @@ Consider updating your local package or contributing to `bun2nix` if this versio
     UnexpectedPackageEntryLength(usize),
     #[error("Failed to render template: '\n{0}'")]
     TemplateError(#[from] askama::Error),
+    #[error("Failed to render custom template: \n{0:#}")]
+    RuntimeTemplateError(#[from] minijinja::Error),
     #[error(
         "Hash was not already known for `{0}`.
 
@@ -62,7 +167,9 @@ This must be prefetched and hashed by `bun2nix` via
 cli, which does not support this as a child process
 needs to be spawned.
 
-Please switch to the native cli instead to use this dependency.
+Please switch to the native cli instead to use this dependency,
+provide a prefetch callback, or pass `--placeholder-hashes`
+to emit `lib.fakeHash` for it.
 "
     )]
     UnsupportedWASMCliAction(String),
@@ -0,0 +1,122 @@
+//! This module handles serializing the package set as JSON, as an alternative to a nix expression
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Package, Provenance,
+    error::{Error, Result},
+    lockfile::WorkspaceLayout,
+};
+
+/// # Json Output
+///
+/// The package set in a stable JSON schema, which can be read by
+/// nix with `builtins.fromJSON` or by any other tool
+///
+/// Any change to the schema which isn't backwards compatible
+/// increments `JsonOutput::SCHEMA_VERSION`
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{JsonOutput, Package, package::Fetcher};
+///
+/// let packages = vec![Package::new(
+///     "zod@3.24.2".to_owned(),
+///     Fetcher::new_npm_package("zod@3.24.2", "sha512-lY7CDW43ECgW9u1TcT3IoXHflywfVqDYze4waEz812jR/bZ8FHDsl7pFQoSZTz5N+2NqRXs8GBwnAwo3ZNxqhQ==".to_owned()).unwrap(),
+/// )];
+///
+/// let json = JsonOutput::new(packages.clone()).render().unwrap();
+///
+/// assert!(json.contains(r#""type": "npm""#));
+/// assert_eq!(json.parse::<JsonOutput>().unwrap().packages, packages);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonOutput {
+    /// The version of the schema the output was written with
+    pub schema_version: u32,
+    /// The provenance of the output, if it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bun2nix: Option<Provenance>,
+    /// Every package in the lockfile, or only those needed
+    /// in production if the output was limited to them
+    pub packages: Vec<Package>,
+    /// The packages only needed by dev dependencies,
+    /// if they were emitted separately
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dev_packages: Vec<Package>,
+    /// The workspaces of the lockfile, keyed by their path, if it has any besides the root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workspaces: BTreeMap<String, WorkspaceLayout>,
+}
+
+impl JsonOutput {
+    /// # Schema Version
+    ///
+    /// The current version of the JSON schema
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    /// # New Json Output
+    ///
+    /// Produce a new, ready to render, JSON document from a package list
+    pub fn new(packages: Vec<Package>) -> Self {
+        Self {
+            schema_version: Self::SCHEMA_VERSION,
+            bun2nix: None,
+            packages,
+            dev_packages: Vec::new(),
+            workspaces: BTreeMap::new(),
+        }
+    }
+
+    /// # With Dev Packages
+    ///
+    /// Record the packages only needed by dev dependencies
+    /// separately, in the `devPackages` field
+    pub fn with_dev_packages(mut self, dev_packages: Vec<Package>) -> Self {
+        self.dev_packages = dev_packages;
+        self
+    }
+
+    /// # With Provenance
+    ///
+    /// Record the provenance of the output in the `bun2nix` field
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.bun2nix = Some(provenance);
+        self
+    }
+
+    /// # With Workspaces
+    ///
+    /// Record the layout of the lockfile's workspaces in the `workspaces` field
+    pub fn with_workspaces(mut self, workspaces: BTreeMap<String, WorkspaceLayout>) -> Self {
+        self.workspaces = workspaces;
+        self
+    }
+
+    /// # Render
+    ///
+    /// Serialize the package set as pretty printed JSON
+    pub fn render(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+
+        Ok(json)
+    }
+}
+
+impl FromStr for JsonOutput {
+    type Err = Error;
+
+    fn from_str(json: &str) -> Result<Self> {
+        let output: Self = serde_json::from_str(json)?;
+
+        if output.schema_version != Self::SCHEMA_VERSION {
+            return Err(Error::UnsupportedJsonSchemaVersion(output.schema_version));
+        }
+
+        Ok(output)
+    }
+}
@@ -3,17 +3,36 @@
 
 #![warn(missing_docs)]
 
+pub mod check;
+pub mod config;
 pub mod error;
+pub mod json_output;
 pub mod lockfile;
 pub mod nix_expression;
 pub mod options;
 pub mod package;
+pub mod prefetch;
+pub mod provenance;
 
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::warn;
+
+pub use check::check_output;
+pub use config::Config;
 pub use error::{Error, Result};
+pub use json_output::JsonOutput;
 pub use lockfile::Lockfile;
-use nix_expression::NixExpression;
-pub use options::Options;
+use lockfile::{
+    DependencyGraph, DependencyPath, GraphExport, GraphFilter, GraphFormat, LockfileDiff,
+    WorkspaceLayout,
+};
+use nix_expression::{NixExpression, SplitNixExpression};
+pub use options::{Options, OutputFormat};
 pub use package::Package;
+use package::{Fetcher, PackageKind};
+use prefetch::KnownHashes;
+pub use provenance::Provenance;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -24,15 +43,386 @@ use wasm_bindgen::prelude::*;
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[cfg_attr(target_arch = "wasm32", no_mangle)]
 pub fn convert_lockfile_to_nix_expression(contents: String, options: Options) -> Result<String> {
+    let lockfile = parse_lockfile(&contents)?;
+    let selection = select_packages(&lockfile, &options)?;
+    let workspaces = workspace_layouts(&lockfile, &selection);
+    let mut packages = lockfile_packages(lockfile, &selection, &options)?;
+
+    hash_packages(&mut packages, &options)?;
+
+    let (packages, dev_packages) = split_dev_packages(packages, selection.production.as_ref());
+
+    render(packages, dev_packages, workspaces, &contents, options)
+}
+
+/// # Convert Bun Lockfile to a Nix expression, prefetching with a callback
+///
+/// The same as `convert_lockfile_to_nix_expression`, but calls back into the
+/// host to hash any source whose hash is not already known, as the WASM build
+/// can't spawn prefetch commands itself.
+///
+/// The callback receives the source url and it's kind (`git`, `github` or
+/// `tarball`), and must return the SRI hash of the source, or a promise of it.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async fn convert_lockfile_to_nix_expression_with_prefetch(
+    contents: String,
+    options: Options,
+    prefetch: Option<js_sys::Function>,
+) -> Result<String> {
+    let lockfile = parse_lockfile(&contents)?;
+    let selection = select_packages(&lockfile, &options)?;
+    let workspaces = workspace_layouts(&lockfile, &selection);
+    let mut packages = lockfile_packages(lockfile, &selection, &options)?;
+
+    if let Some(prefetch) = prefetch.filter(|_| !options.placeholder_hashes && !options.offline) {
+        prefetch::prefetch_packages_with_callback(&mut packages, &prefetch).await?;
+    }
+
+    hash_packages(&mut packages, &options)?;
+
+    let (packages, dev_packages) = split_dev_packages(packages, selection.production.as_ref());
+
+    render(packages, dev_packages, workspaces, &contents, options)
+}
+
+/// # Convert Bun Lockfile to a set of Nix files
+///
+/// Takes a string input of the contents of a bun lockfile and converts it into a
+/// set of Nix files, keyed by their path relative to the output directory, which
+/// split the packages by the workspaces which use them
+///
+/// The `default.nix` file of the set can be consumed in the same way as
+/// the single expression produced by `convert_lockfile_to_nix_expression`
+pub fn convert_lockfile_to_nix_files(
+    contents: String,
+    options: Options,
+) -> Result<BTreeMap<String, String>> {
+    if options.format != OutputFormat::Nix {
+        return Err(Error::UnsupportedSplitOutputFormat);
+    }
+
+    let lockfile = parse_lockfile(&contents)?;
+    let graph = DependencyGraph::new(&lockfile);
+    let selection = select_packages(&lockfile, &options)?;
+    let workspaces = workspace_layouts(&lockfile, &selection);
+    let mut packages = lockfile_packages(lockfile, &selection, &options)?;
+
+    hash_packages(&mut packages, &options)?;
+
+    let (packages, dev_packages) = split_dev_packages(packages, selection.production.as_ref());
+
+    SplitNixExpression::new(packages, &graph)?
+        .with_dev_packages(dev_packages)
+        .with_provenance(Provenance::new(&contents, NixExpression::SCHEMA_VERSION))
+        .with_workspaces(workspaces)
+        .render_with_options(options)
+}
+
+/// # Diff Bun Lockfiles
+///
+/// Takes the contents of two bun lockfiles and summarizes how their packages
+/// changed, including any new sources which will need to be prefetched
+pub fn diff_lockfiles(old: &str, new: &str) -> Result<LockfileDiff> {
+    Ok(LockfileDiff::new(
+        &parse_lockfile(old)?,
+        &parse_lockfile(new)?,
+    ))
+}
+
+/// # Explain Why a Package is in a Bun Lockfile
+///
+/// Takes the contents of a bun lockfile and finds the shortest chain of
+/// dependencies leading to each package matching a name through each direct
+/// dependency of every workspace, where the name may
+/// either be a bare package name or include it's version
+pub fn explain_package(
+    contents: &str,
+    query: &str,
+) -> Result<BTreeMap<String, Vec<DependencyPath>>> {
+    let graph = DependencyGraph::new(&parse_lockfile(contents)?);
+
+    let paths: BTreeMap<String, Vec<DependencyPath>> = graph
+        .find(query)
+        .into_iter()
+        .map(|name| (name.to_owned(), graph.paths_to(name)))
+        .collect();
+
+    if paths.is_empty() {
+        return Err(Error::PackageNotFound(query.to_owned()));
+    }
+
+    Ok(paths)
+}
+
+/// # Export the Dependency Graph of a Bun Lockfile
+///
+/// Takes the contents of a bun lockfile and renders the part of it's
+/// dependency graph selected by a filter, whose workspace may be given
+/// as either it's path or it's name
+pub fn export_dependency_graph(
+    contents: &str,
+    mut filter: GraphFilter,
+    format: GraphFormat,
+) -> Result<String> {
+    let lockfile = parse_lockfile(contents)?;
+
+    if let Some(workspace) = &filter.workspace {
+        filter.workspace = Some(
+            lockfile
+                .find_workspace(workspace)
+                .ok_or_else(|| Error::WorkspaceNotFound(workspace.to_owned()))?,
+        );
+    }
+
+    GraphExport::new(&DependencyGraph::new(&lockfile), &filter).render(format)
+}
+
+/// Parse a lockfile, checking it is of a supported version
+fn parse_lockfile(contents: &str) -> Result<Lockfile> {
     let lockfile = contents.parse::<Lockfile>()?;
 
     if lockfile.lockfile_version != 1 {
         return Err(Error::UnsupportedLockfileVersion(lockfile.lockfile_version));
     };
 
+    Ok(lockfile)
+}
+
+/// Describe the workspaces of a lockfile, if it has any besides the root,
+/// leaving out every other workspace if the output is scoped to one
+fn workspace_layouts(
+    lockfile: &Lockfile,
+    selection: &PackageSelection,
+) -> BTreeMap<String, WorkspaceLayout> {
+    if !lockfile.has_workspaces() {
+        return BTreeMap::new();
+    }
+
+    let mut layouts = lockfile.workspace_layouts();
+
+    if let Some(workspace) = &selection.workspace {
+        layouts.retain(|path, _| path == workspace);
+    }
+
+    layouts
+}
+
+/// Which of a lockfile's packages to emit
+#[derive(Default)]
+struct PackageSelection {
+    /// The path of the workspace the output is scoped to, if any
+    workspace: Option<String>,
+    /// The names of the packages to emit, if the options
+    /// limit them to those installed for a single workspace
+    scope: Option<BTreeSet<String>>,
+    /// The names of the packages needed in production,
+    /// if the options only call for those
+    production: Option<BTreeSet<String>>,
+}
+
+/// Select which of a lockfile's packages to emit, as configured by the options
+fn select_packages(lockfile: &Lockfile, options: &Options) -> Result<PackageSelection> {
+    if options.workspace.is_none() && (!options.production || lockfile.workspaces().is_empty()) {
+        return Ok(PackageSelection::default());
+    }
+
+    let graph = DependencyGraph::new(lockfile);
+
+    let workspace = options
+        .workspace
+        .as_ref()
+        .map(|workspace| {
+            lockfile
+                .find_workspace(workspace)
+                .ok_or_else(|| Error::WorkspaceNotFound(workspace.to_owned()))
+        })
+        .transpose()?;
+
+    let workspaces = match &workspace {
+        Some(workspace) => vec![workspace.to_owned()],
+        None => graph.workspaces.keys().cloned().collect(),
+    };
+
+    Ok(PackageSelection {
+        workspace,
+        scope: options
+            .workspace
+            .as_ref()
+            .map(|_| installed_packages(lockfile, &graph, &workspaces, true)),
+        production: options
+            .production
+            .then(|| installed_packages(lockfile, &graph, &workspaces, false)),
+    })
+}
+
+/// The names of the packages bun installs for a set of workspaces,
+/// alongside the packages of the workspaces themselves, which
+/// bun always links, even when installing for production
+fn installed_packages(
+    lockfile: &Lockfile,
+    graph: &DependencyGraph,
+    workspaces: &[String],
+    dev: bool,
+) -> BTreeSet<String> {
+    let mut installed = graph.install_closure(workspaces.iter().map(String::as_str), dev);
+
+    installed.extend(
+        lockfile
+            .packages
+            .iter()
+            .filter(|pkg| match (&pkg.metadata.kind, &pkg.fetcher) {
+                (PackageKind::Workspace, Fetcher::CopyToStore { path }) => {
+                    workspaces.contains(&DependencyGraph::workspace_path(path))
+                }
+                _ => false,
+            })
+            .map(|pkg| pkg.name.to_owned()),
+    );
+
+    installed
+}
+
+/// Take the packages out of a lockfile, overriding their sources, rewriting
+/// their urls and filling in any hashes already known, and leaving out those
+/// which weren't selected, or are only needed by dev dependencies unless
+/// they are to be emitted separately
+fn lockfile_packages(
+    lockfile: Lockfile,
+    selection: &PackageSelection,
+    options: &Options,
+) -> Result<Vec<Package>> {
     let mut packages = lockfile.packages();
     packages.sort();
     packages.dedup_by(|a, b| a.name == b.name);
 
-    NixExpression::new(packages)?.render_with_options(options)
+    override_fetchers(&mut packages, &options.fetcher_overrides)?;
+
+    if let Some(scope) = &selection.scope {
+        packages.retain(|pkg| scope.contains(&pkg.name));
+    }
+
+    if let Some(production) = selection
+        .production
+        .as_ref()
+        .filter(|_| !options.dev_packages)
+    {
+        packages.retain(|pkg| production.contains(&pkg.name));
+    }
+
+    for pkg in &mut packages {
+        pkg.fetcher.rewrite_urls(&options.url_rewrites);
+    }
+
+    if let Some(existing_output) = &options.existing_output {
+        let known_hashes = KnownHashes::from_existing_output(existing_output);
+
+        // A source already named in the output, when none could be found
+        // in it, means the output was not in a format that could be read
+        let unreadable = !known_hashes.found_sources()
+            && packages
+                .iter()
+                .any(|pkg| pkg.fetcher.needs_prefetch() && existing_output.contains(&pkg.name));
+
+        if unreadable {
+            warn!(
+                "No hashes could be reused from the existing output, which is only read when it was rendered with a built-in template"
+            );
+        }
+
+        known_hashes.fill_packages(&mut packages);
+    }
+
+    Ok(packages)
+}
+
+/// Replace the sources of packages with those given in the options,
+/// failing if any override names a package which isn't in the lockfile
+fn override_fetchers(
+    packages: &mut [Package],
+    overrides: &BTreeMap<String, Fetcher>,
+) -> Result<()> {
+    let unknown: Vec<String> = overrides
+        .keys()
+        .filter(|name| !packages.iter().any(|pkg| &pkg.name == *name))
+        .cloned()
+        .collect();
+
+    if !unknown.is_empty() {
+        return Err(Error::UnknownFetcherOverrides(unknown));
+    }
+
+    for pkg in packages {
+        let Some(fetcher) = overrides.get(&pkg.name) else {
+            continue;
+        };
+
+        // Paths are normalized in the same way as those from the lockfile
+        let fetcher = match fetcher {
+            Fetcher::CopyToStore { path } => Fetcher::new_copy_to_store(path)?,
+            fetcher => fetcher.clone(),
+        };
+
+        pkg.override_fetcher(fetcher);
+    }
+
+    Ok(())
+}
+
+/// Split the packages into those needed in production and the
+/// remainder only needed by dev dependencies, if there are any
+fn split_dev_packages(
+    packages: Vec<Package>,
+    production: Option<&BTreeSet<String>>,
+) -> (Vec<Package>, Vec<Package>) {
+    match production {
+        Some(production) => packages
+            .into_iter()
+            .partition(|pkg| production.contains(&pkg.name)),
+        None => (packages, Vec::new()),
+    }
+}
+
+/// Render the packages in the configured output format, alongside the
+/// layout of the workspaces and provenance of the lockfile they were read from
+fn render(
+    packages: Vec<Package>,
+    dev_packages: Vec<Package>,
+    workspaces: BTreeMap<String, WorkspaceLayout>,
+    contents: &str,
+    options: Options,
+) -> Result<String> {
+    match options.format {
+        OutputFormat::Nix => NixExpression::new(packages)?
+            .with_dev_packages(dev_packages)
+            .with_provenance(Provenance::new(contents, NixExpression::SCHEMA_VERSION))
+            .with_workspaces(workspaces)
+            .render_with_options(options),
+        OutputFormat::Json => JsonOutput::new(packages)
+            .with_dev_packages(dev_packages)
+            .with_provenance(Provenance::new(contents, JsonOutput::SCHEMA_VERSION))
+            .with_workspaces(workspaces)
+            .render(),
+    }
+}
+
+/// Hash any packages which still need it, as configured by the options
+fn hash_packages(packages: &mut [Package], options: &Options) -> Result<()> {
+    if options.placeholder_hashes {
+        prefetch::warn_placeholder_hashes(packages);
+
+        return Ok(());
+    }
+
+    let prefetcher = options.prefetcher();
+
+    if options.offline {
+        let unhashed = prefetch::unhashed_sources(packages, prefetcher.as_ref());
+
+        if !unhashed.is_empty() {
+            return Err(Error::OfflineUnhashedSources(unhashed));
+        }
+    }
+
+    prefetch::prefetch_packages(packages, prefetcher.as_ref(), options.prefetch_retries)
 }
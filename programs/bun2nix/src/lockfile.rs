@@ -1,7 +1,10 @@
 //! This module holds everything related to deserialization of the bun lockfile, including type
 //! mappings and custom deserialization methods
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
 
 use log::warn;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -12,12 +15,20 @@ use crate::{
     error::{Error, Result},
 };
 
+mod dependency_graph;
+mod graph_export;
+mod lockfile_diff;
 mod package_deserializer;
 mod package_visitor;
+mod workspace_layout;
+pub use dependency_graph::{Dependency, DependencyGraph, DependencyKind, DependencyPath};
+pub use graph_export::{GraphEdge, GraphExport, GraphFilter, GraphFormat, GraphNode};
+pub use lockfile_diff::{LockfileDiff, PackageChange, PackageSummary};
 pub use package_deserializer::{
     PackageDeserializer, drop_prefix, split_once_owned, swap_remove_value,
 };
 pub use package_visitor::PackageVisitor;
+pub use workspace_layout::WorkspaceLayout;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -102,6 +113,76 @@ impl Lockfile {
         self.workspaces.iter().any(|(key, _)| !key.is_empty())
     }
 
+    /// # Find Workspace
+    ///
+    /// Find a workspace by either it's path or it's name, returning
+    /// it's path as used in the generated output
+    pub fn find_workspace(&self, query: &str) -> Option<String> {
+        let query_path = query.trim_start_matches("./").trim_end_matches('/');
+
+        self.workspaces
+            .iter()
+            .find(|(path, workspace)| {
+                DependencyGraph::workspace_path(path) == query
+                    || (!query_path.is_empty() && path.as_str() == query_path)
+                    || workspace.name.as_deref() == Some(query)
+            })
+            .map(|(path, _)| DependencyGraph::workspace_path(path))
+    }
+
+    /// # Workspace Layouts
+    ///
+    /// Describe every workspace in the lockfile, keyed by it's path
+    /// relative to the project root, with `.` being the root itself
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::Lockfile;
+    ///
+    /// let lockfile: Lockfile = r#"
+    /// {
+    ///   "lockfileVersion": 1,
+    ///   "workspaces": {
+    ///     "": {
+    ///       "name": "root",
+    ///     },
+    ///     "packages/app": {
+    ///       "name": "app",
+    ///       "version": "1.0.0",
+    ///       "dependencies": {
+    ///         "is-odd": "^3.0.1",
+    ///       },
+    ///     },
+    ///   },
+    ///   "packages": {
+    ///     "app": ["app@workspace:packages/app"],
+    ///     "is-odd": ["is-odd@3.0.1", "", {}, "sha512-CQpnWPrDwmP1+SMHXZhtLtJv90yiyVfluGsX5iNCVkrhQtU3TQHsUWPG9wkdk9Lgd5yNpAg9jQEo90CBaXgWMA=="],
+    ///   }
+    /// }
+    /// "#
+    /// .parse()
+    /// .unwrap();
+    ///
+    /// let layouts = lockfile.workspace_layouts();
+    ///
+    /// assert_eq!(layouts["."].name.as_deref(), Some("root"));
+    /// assert_eq!(layouts["packages/app"].version.as_deref(), Some("1.0.0"));
+    /// assert_eq!(layouts["packages/app"].dependencies, vec!["is-odd@3.0.1"]);
+    /// ```
+    pub fn workspace_layouts(&self) -> BTreeMap<String, WorkspaceLayout> {
+        let graph = DependencyGraph::new(self);
+
+        self.workspaces
+            .iter()
+            .map(|(path, workspace)| {
+                let path = DependencyGraph::workspace_path(path);
+                let dependencies = graph.workspaces.get(&path).into_iter().flatten();
+
+                (path, WorkspaceLayout::new(workspace, dependencies))
+            })
+            .collect()
+    }
+
     /// # Parse to Value
     ///
     /// Parse the lockfile into a serde json value
@@ -142,6 +223,9 @@ pub struct Workspace {
     /// The name of the workspace
     pub name: Option<String>,
 
+    /// The version of the workspace
+    pub version: Option<String>,
+
     /// Dependencies of the workspace
     #[serde(default, deserialize_with = "Workspace::deserialize_dependencies")]
     pub dependencies: Dependencies,
@@ -149,6 +233,14 @@ pub struct Workspace {
     /// Dev dependencies of the workspace
     #[serde(default, deserialize_with = "Workspace::deserialize_dependencies")]
     pub dev_dependencies: Dependencies,
+
+    /// Optional dependencies of the workspace
+    #[serde(default, deserialize_with = "Workspace::deserialize_dependencies")]
+    pub optional_dependencies: Dependencies,
+
+    /// Peer dependencies of the workspace
+    #[serde(default, deserialize_with = "Workspace::deserialize_dependencies")]
+    pub peer_dependencies: Dependencies,
 }
 
 impl Workspace {
@@ -0,0 +1,447 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::Lockfile;
+use crate::package::{Fetcher, PackageKind};
+
+/// # Dependency Kind
+///
+/// The kind of edge from a package or workspace to one of it's dependencies
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    /// A regular dependency, needed at runtime
+    Prod,
+    /// A dev dependency, only declared by workspaces
+    Dev,
+    /// An optional dependency
+    Optional,
+    /// A peer dependency, which was installed
+    Peer,
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Prod => "prod",
+            Self::Dev => "dev",
+            Self::Optional => "optional",
+            Self::Peer => "peer",
+        })
+    }
+}
+
+/// # Dependency
+///
+/// An edge from a package or workspace to one of it's dependencies
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Dependency {
+    /// The name of the dependency, which is it's name in `package.json`
+    /// before being resolved, and it's package name after
+    pub name: String,
+
+    /// The kind of dependency
+    pub kind: DependencyKind,
+}
+
+impl Dependency {
+    /// # New Dependency
+    ///
+    /// Create a dependency of a given kind
+    pub fn new(name: String, kind: DependencyKind) -> Self {
+        Self { name, kind }
+    }
+}
+
+/// # Dependency Path
+///
+/// A chain of dependencies leading from a workspace to a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyPath {
+    /// The path of the workspace the chain starts at
+    pub workspace: String,
+
+    /// Each dependency in the chain, ending with the package
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Renders the path as `workspace -> package -> package (dev)`,
+/// marking every edge which isn't a regular dependency
+impl fmt::Display for DependencyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.workspace)?;
+
+        for dependency in &self.dependencies {
+            write!(f, " -> {}", dependency.name)?;
+
+            if dependency.kind != DependencyKind::Prod {
+                write!(f, " ({})", dependency.kind)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// # Dependency Graph
+///
+/// The resolved dependencies of every package and workspace in a lockfile,
+/// where packages are identified by their name in the generated output, and
+/// workspaces by their path relative to the project root
+///
+/// Workspace packages share the dependencies of the workspace they point to
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{Lockfile, lockfile::{DependencyGraph, DependencyKind}};
+///
+/// let lockfile: Lockfile = r#"
+/// {
+///   "lockfileVersion": 1,
+///   "workspaces": {
+///     "": {
+///       "name": "root",
+///       "dependencies": {
+///         "is-odd": "^3.0.1",
+///       },
+///     },
+///   },
+///   "packages": {
+///     "is-number": ["is-number@6.0.0", "", {}, "sha512-Wu1VHeILBK8KAWJUAiSZQX94GmOE45Rg6/538fKwiloUu21KncEkYGPqob2oSZ5mUT73vLGrHQjKw3KMPwfDzg=="],
+///     "is-odd": ["is-odd@3.0.1", "", { "dependencies": { "is-number": "^6.0.0" } }, "sha512-CQpnWPrDwmP1+SMHXZhtLtJv90yiyVfluGsX5iNCVkrhQtU3TQHsUWPG9wkdk9Lgd5yNpAg9jQEo90CBaXgWMA=="],
+///   }
+/// }
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// let graph = DependencyGraph::new(&lockfile);
+///
+/// assert_eq!(graph.dependencies("is-odd@3.0.1")[0].name, "is-number@6.0.0");
+/// assert_eq!(
+///     graph.workspace_closure(".", |_| true).into_iter().collect::<Vec<_>>(),
+///     vec!["is-number@6.0.0", "is-odd@3.0.1"]
+/// );
+///
+/// assert_eq!(graph.find("is-number"), vec!["is-number@6.0.0"]);
+/// assert_eq!(
+///     graph.paths_to("is-number@6.0.0")[0].to_string(),
+///     ". -> is-odd@3.0.1 -> is-number@6.0.0"
+/// );
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct DependencyGraph {
+    /// The resolved dependencies of every package, keyed by it's name
+    pub packages: BTreeMap<String, Vec<Dependency>>,
+
+    /// The resolved dependencies of every workspace, keyed by it's path
+    pub workspaces: BTreeMap<String, Vec<Dependency>>,
+}
+
+impl DependencyGraph {
+    /// # New Dependency Graph
+    ///
+    /// Resolve the dependencies of every package and workspace in a lockfile
+    pub fn new(lockfile: &Lockfile) -> Self {
+        let keys: HashMap<&str, &str> = lockfile
+            .packages
+            .iter()
+            .map(|pkg| (pkg.key.as_str(), pkg.name.as_str()))
+            .collect();
+
+        let workspaces: BTreeMap<String, Vec<Dependency>> = lockfile
+            .workspaces
+            .iter()
+            .map(|(path, workspace)| {
+                let scope = workspace.name.as_deref().unwrap_or_default();
+
+                let dependencies = [
+                    (&workspace.dependencies, DependencyKind::Prod),
+                    (&workspace.dev_dependencies, DependencyKind::Dev),
+                    (&workspace.optional_dependencies, DependencyKind::Optional),
+                    (&workspace.peer_dependencies, DependencyKind::Peer),
+                ]
+                .into_iter()
+                .flat_map(|(dependencies, kind)| {
+                    dependencies.keys().map(move |name| (name.as_str(), kind))
+                });
+
+                (
+                    Self::workspace_path(path),
+                    Self::resolve(&keys, scope, dependencies),
+                )
+            })
+            .collect();
+
+        let mut packages: BTreeMap<String, Vec<Dependency>> = BTreeMap::new();
+
+        for pkg in &lockfile.packages {
+            let dependencies = match (&pkg.metadata.kind, &pkg.fetcher) {
+                (PackageKind::Workspace, Fetcher::CopyToStore { path }) => workspaces
+                    .get(&Self::workspace_path(path))
+                    .cloned()
+                    .unwrap_or_default(),
+                _ => Self::resolve(
+                    &keys,
+                    &pkg.key,
+                    pkg.dependencies
+                        .iter()
+                        .map(|dependency| (dependency.name.as_str(), dependency.kind)),
+                ),
+            };
+
+            // The same package may be found under multiple keys
+            let entry = packages.entry(pkg.name.to_owned()).or_default();
+            entry.extend(dependencies);
+            entry.sort();
+            entry.dedup();
+        }
+
+        Self {
+            packages,
+            workspaces,
+        }
+    }
+
+    /// # Workspace Path
+    ///
+    /// The path of a workspace as used in the generated output,
+    /// where the project root is written as `.` rather than
+    /// the empty string bun uses
+    pub fn workspace_path(path: &str) -> String {
+        match path {
+            "" => ".".to_owned(),
+            path => path.to_owned(),
+        }
+    }
+
+    /// # Dependencies
+    ///
+    /// Get the resolved dependencies of a package
+    pub fn dependencies(&self, name: &str) -> &[Dependency] {
+        self.packages
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// # Closure
+    ///
+    /// Find every package transitively reachable from a set of
+    /// dependencies, following only the kinds of edges accepted
+    pub fn closure<'a>(
+        &self,
+        roots: impl IntoIterator<Item = &'a Dependency>,
+        follow: impl Fn(DependencyKind) -> bool,
+    ) -> BTreeSet<String> {
+        let mut reachable = BTreeSet::new();
+        let mut queue: VecDeque<&Dependency> = roots.into_iter().collect();
+
+        while let Some(dependency) = queue.pop_front() {
+            if !follow(dependency.kind) || !reachable.insert(dependency.name.to_owned()) {
+                continue;
+            }
+
+            queue.extend(self.dependencies(&dependency.name));
+        }
+
+        reachable
+    }
+
+    /// # Workspace Closure
+    ///
+    /// Find every package transitively reachable from a workspace,
+    /// following only the kinds of edges accepted
+    pub fn workspace_closure(
+        &self,
+        path: &str,
+        follow: impl Fn(DependencyKind) -> bool,
+    ) -> BTreeSet<String> {
+        self.closure(self.workspaces.get(path).into_iter().flatten(), follow)
+    }
+
+    /// # Install Closure
+    ///
+    /// Find every package bun installs for a set of workspaces, which is
+    /// every package reachable from their dependencies, only including
+    /// their dev dependencies if asked to, as with `bun install --production`
+    ///
+    /// The dev dependencies of packages, including workspace
+    /// packages, are never installed
+    pub fn install_closure<'a>(
+        &self,
+        workspaces: impl IntoIterator<Item = &'a str>,
+        dev: bool,
+    ) -> BTreeSet<String> {
+        let roots: Vec<Dependency> = workspaces
+            .into_iter()
+            .filter_map(|path| self.workspaces.get(path))
+            .flatten()
+            .filter(|dependency| dev || dependency.kind != DependencyKind::Dev)
+            .map(|dependency| Dependency::new(dependency.name.to_owned(), DependencyKind::Prod))
+            .collect();
+
+        self.closure(&roots, |kind| kind != DependencyKind::Dev)
+    }
+
+    /// # Find
+    ///
+    /// Find the packages matching a name, which may either
+    /// be a bare package name or include it's version
+    pub fn find(&self, query: &str) -> Vec<&str> {
+        self.packages
+            .keys()
+            .filter(|name| {
+                name.strip_prefix(query)
+                    .is_some_and(|version| version.is_empty() || version.starts_with('@'))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// # Paths To
+    ///
+    /// Find the shortest chain of dependencies leading to a package through
+    /// each direct dependency of every workspace, which keeps the number of
+    /// chains bounded even when the graph has many routes to the package
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::lockfile::{Dependency, DependencyGraph, DependencyKind};
+    ///
+    /// // A chain of diamonds, with 2^20 distinct routes from `a0` to `a20`
+    /// let mut graph = DependencyGraph::default();
+    ///
+    /// for i in 0..20 {
+    ///     let dependency = |name: String| Dependency::new(name, DependencyKind::Prod);
+    ///
+    ///     graph.packages.insert(
+    ///         format!("a{}", i),
+    ///         vec![dependency(format!("b{}", i)), dependency(format!("c{}", i))],
+    ///     );
+    ///     graph.packages.insert(format!("b{}", i), vec![dependency(format!("a{}", i + 1))]);
+    ///     graph.packages.insert(format!("c{}", i), vec![dependency(format!("a{}", i + 1))]);
+    /// }
+    ///
+    /// graph.packages.insert("a20".to_owned(), vec![]);
+    /// graph.workspaces.insert(
+    ///     ".".to_owned(),
+    ///     vec![Dependency::new("a0".to_owned(), DependencyKind::Prod)],
+    /// );
+    ///
+    /// let paths = graph.paths_to("a20");
+    ///
+    /// assert_eq!(paths.len(), 1);
+    /// assert_eq!(paths[0].dependencies.len(), 41);
+    /// ```
+    pub fn paths_to(&self, target: &str) -> Vec<DependencyPath> {
+        self.workspaces
+            .iter()
+            .flat_map(|(workspace, dependencies)| {
+                dependencies
+                    .iter()
+                    .filter_map(|dependency| self.shortest_chain(dependency, target))
+                    .map(|dependencies| DependencyPath {
+                        workspace: workspace.to_owned(),
+                        dependencies,
+                    })
+            })
+            .collect()
+    }
+
+    /// Search breadth first from a dependency for the shortest
+    /// chain of dependencies leading to the target, if any
+    fn shortest_chain(&self, start: &Dependency, target: &str) -> Option<Vec<Dependency>> {
+        // The dependency each package was first reached by, and it's dependent
+        let mut reached: HashMap<&str, (Option<&str>, &Dependency)> =
+            HashMap::from([(start.name.as_str(), (None, start))]);
+        let mut queue = VecDeque::from([start.name.as_str()]);
+
+        while let Some(name) = queue.pop_front() {
+            if name == target {
+                let mut chain = Vec::new();
+                let mut current = Some(name);
+
+                while let Some((dependent, dependency)) = current.and_then(|name| reached.get(name))
+                {
+                    chain.push((*dependency).clone());
+                    current = *dependent;
+                }
+
+                chain.reverse();
+
+                return Some(chain);
+            }
+
+            for dependency in self.dependencies(name) {
+                if !reached.contains_key(dependency.name.as_str()) {
+                    reached.insert(&dependency.name, (Some(name), dependency));
+                    queue.push_back(&dependency.name);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Resolve dependency names to package names, in the same way as bun
+    /// does, by looking for the dependency nested under it's dependent's key,
+    /// and then under each of it's parents in turn
+    fn resolve<'a>(
+        keys: &HashMap<&str, &str>,
+        scope: &str,
+        dependencies: impl Iterator<Item = (&'a str, DependencyKind)>,
+    ) -> Vec<Dependency> {
+        let segments = Self::key_segments(scope);
+
+        let mut resolved: Vec<Dependency> = dependencies
+            .filter_map(|(name, kind)| {
+                (0..=segments.len()).rev().find_map(|depth| {
+                    let key = segments[..depth]
+                        .iter()
+                        .chain([&name])
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join("/");
+
+                    keys.get(key.as_str())
+                        .map(|resolved| Dependency::new(resolved.to_string(), kind))
+                })
+            })
+            .collect();
+
+        resolved.sort();
+        resolved.dedup();
+
+        resolved
+    }
+
+    /// Split a lockfile key into the package names it is nested under,
+    /// keeping the scope of scoped packages attached to their name
+    fn key_segments(key: &str) -> Vec<&str> {
+        let mut segments = Vec::new();
+        let mut rest = key;
+
+        while !rest.is_empty() {
+            let end = match rest.find('/') {
+                Some(slash) if rest.starts_with('@') => rest[slash + 1..]
+                    .find('/')
+                    .map_or(rest.len(), |next| slash + 1 + next),
+                Some(slash) => slash,
+                None => rest.len(),
+            };
+
+            segments.push(&rest[..end]);
+            rest = rest.get(end + 1..).unwrap_or_default();
+        }
+
+        segments
+    }
+}
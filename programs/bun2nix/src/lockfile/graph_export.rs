@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use super::{Dependency, DependencyGraph, DependencyKind};
+use crate::error::Result;
+
+/// # Graph Format
+///
+/// The format to write an exported dependency graph in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// A Graphviz DOT digraph
+    #[default]
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+    /// A JSON document of nodes and edges
+    Json,
+}
+
+/// # Graph Filter
+///
+/// Which part of a dependency graph to export
+#[derive(Debug, Clone)]
+pub struct GraphFilter {
+    /// Only export the dependencies of the workspace at this path,
+    /// rather than those of every workspace
+    pub workspace: Option<String>,
+
+    /// How many edges away from a workspace to export packages
+    pub depth: Option<usize>,
+
+    /// The kinds of edges to follow
+    pub kinds: Vec<DependencyKind>,
+}
+
+impl Default for GraphFilter {
+    fn default() -> Self {
+        Self {
+            workspace: None,
+            depth: None,
+            kinds: vec![
+                DependencyKind::Prod,
+                DependencyKind::Dev,
+                DependencyKind::Optional,
+                DependencyKind::Peer,
+            ],
+        }
+    }
+}
+
+/// # Graph Node
+///
+/// A workspace or package in an exported dependency graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphNode {
+    /// The path of the workspace, or name of the package
+    pub id: String,
+
+    /// Whether the node is a workspace rather than a package
+    pub workspace: bool,
+}
+
+/// # Graph Edge
+///
+/// A dependency between two nodes of an exported dependency graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge {
+    /// The id of the dependent node
+    pub from: String,
+
+    /// The id of the dependency
+    pub to: String,
+
+    /// The kind of dependency
+    pub kind: DependencyKind,
+}
+
+/// # Graph Export
+///
+/// The nodes and edges of the part of a dependency graph selected by
+/// a `GraphFilter`, which can be rendered for use in other tools
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{Lockfile, lockfile::{DependencyGraph, GraphExport, GraphFilter, GraphFormat}};
+///
+/// let lockfile: Lockfile = r#"
+/// {
+///   "lockfileVersion": 1,
+///   "workspaces": {
+///     "": {
+///       "name": "root",
+///       "dependencies": {
+///         "is-odd": "^3.0.1",
+///       },
+///     },
+///   },
+///   "packages": {
+///     "is-number": ["is-number@6.0.0", "", {}, "sha512-Wu1VHeILBK8KAWJUAiSZQX94GmOE45Rg6/538fKwiloUu21KncEkYGPqob2oSZ5mUT73vLGrHQjKw3KMPwfDzg=="],
+///     "is-odd": ["is-odd@3.0.1", "", { "dependencies": { "is-number": "^6.0.0" } }, "sha512-CQpnWPrDwmP1+SMHXZhtLtJv90yiyVfluGsX5iNCVkrhQtU3TQHsUWPG9wkdk9Lgd5yNpAg9jQEo90CBaXgWMA=="],
+///   }
+/// }
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// let export = GraphExport::new(
+///     &DependencyGraph::new(&lockfile),
+///     &GraphFilter {
+///         depth: Some(1),
+///         ..Default::default()
+///     },
+/// );
+///
+/// assert_eq!(export.nodes.len(), 2);
+/// assert!(
+///     export
+///         .render(GraphFormat::Dot)
+///         .unwrap()
+///         .contains(r#""." -> "is-odd@3.0.1";"#)
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct GraphExport {
+    /// Every workspace and package in the graph
+    pub nodes: Vec<GraphNode>,
+
+    /// Every dependency between the nodes of the graph
+    pub edges: Vec<GraphEdge>,
+}
+
+impl GraphExport {
+    /// # New Graph Export
+    ///
+    /// Select the part of a dependency graph to export, walking out
+    /// from the workspaces and following only the accepted kinds of edges
+    pub fn new(graph: &DependencyGraph, filter: &GraphFilter) -> Self {
+        let workspaces = graph
+            .workspaces
+            .iter()
+            .filter(|(path, _)| filter.workspace.as_ref().is_none_or(|only| only == *path));
+
+        let mut nodes = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+
+        // Packages are visited breadth first, so each is first
+        // seen at the fewest edges it is away from a workspace
+        let mut seen: BTreeSet<&str> = BTreeSet::new();
+        let mut queue: VecDeque<(&str, &[Dependency], usize)> = VecDeque::new();
+
+        for (path, dependencies) in workspaces {
+            nodes.insert(GraphNode {
+                id: path.to_owned(),
+                workspace: true,
+            });
+
+            queue.push_back((path, dependencies, 0));
+        }
+
+        while let Some((from, dependencies, depth)) = queue.pop_front() {
+            if filter.depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+
+            for dependency in dependencies {
+                if !filter.kinds.contains(&dependency.kind) {
+                    continue;
+                }
+
+                edges.insert(GraphEdge {
+                    from: from.to_owned(),
+                    to: dependency.name.to_owned(),
+                    kind: dependency.kind,
+                });
+
+                if !seen.insert(&dependency.name) {
+                    continue;
+                }
+
+                nodes.insert(GraphNode {
+                    id: dependency.name.to_owned(),
+                    workspace: false,
+                });
+
+                queue.push_back((
+                    &dependency.name,
+                    graph.dependencies(&dependency.name),
+                    depth + 1,
+                ));
+            }
+        }
+
+        Self {
+            nodes: nodes.into_iter().collect(),
+            edges: edges.into_iter().collect(),
+        }
+    }
+
+    /// # Render
+    ///
+    /// Render the graph in the given format
+    pub fn render(&self, format: GraphFormat) -> Result<String> {
+        match format {
+            GraphFormat::Dot => Ok(self.render_dot()),
+            GraphFormat::Mermaid => Ok(self.render_mermaid()),
+            GraphFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+        }
+    }
+
+    /// Render as a DOT digraph, with workspaces drawn as boxes and
+    /// any edge which isn't a regular dependency dashed and labelled
+    fn render_dot(&self) -> String {
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut dot = String::from("digraph dependencies {\n");
+
+        for node in &self.nodes {
+            match node.workspace {
+                true => dot += &format!("  {} [shape=box];\n", quote(&node.id)),
+                false => dot += &format!("  {};\n", quote(&node.id)),
+            }
+        }
+
+        for edge in &self.edges {
+            dot += &format!("  {} -> {}", quote(&edge.from), quote(&edge.to));
+
+            if edge.kind != DependencyKind::Prod {
+                dot += &format!(" [style=dashed, label=\"{}\"]", edge.kind);
+            }
+
+            dot += ";\n";
+        }
+
+        dot + "}\n"
+    }
+
+    /// Render as a Mermaid flowchart, where nodes are given numbered ids
+    /// as package names aren't valid ids, and are labelled with their names
+    fn render_mermaid(&self) -> String {
+        let ids: BTreeMap<&str, String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+            .collect();
+
+        let mut mermaid = String::from("flowchart LR\n");
+
+        for node in &self.nodes {
+            let label = node.id.replace('"', "#quot;");
+
+            match node.workspace {
+                true => mermaid += &format!("  {}[[\"{}\"]]\n", ids[node.id.as_str()], label),
+                false => mermaid += &format!("  {}[\"{}\"]\n", ids[node.id.as_str()], label),
+            }
+        }
+
+        for edge in &self.edges {
+            let (from, to) = (&ids[edge.from.as_str()], &ids[edge.to.as_str()]);
+
+            match edge.kind {
+                DependencyKind::Prod => mermaid += &format!("  {} --> {}\n", from, to),
+                kind => mermaid += &format!("  {} -. {} .-> {}\n", from, kind, to),
+            }
+        }
+
+        mermaid
+    }
+}
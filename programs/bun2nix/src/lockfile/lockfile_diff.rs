@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use super::Lockfile;
+use crate::{
+    Package,
+    package::{Fetcher, PackageKind},
+};
+
+/// # Package Summary
+///
+/// The version and kind of a package, as shown in a `LockfileDiff`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageSummary {
+    /// The version of the package, or where it is fetched
+    /// from if it is not an npm package
+    pub version: String,
+
+    /// What kind of dependency the package is
+    pub kind: PackageKind,
+}
+
+impl PackageSummary {
+    /// # New Package Summary
+    ///
+    /// Summarize the version and kind of a package
+    pub fn new(pkg: &Package) -> Self {
+        let version = match (&pkg.metadata.version, &pkg.fetcher) {
+            (Some(version), _) => version.to_owned(),
+            (None, Fetcher::CopyToStore { path }) => path.to_owned(),
+            (None, fetcher) => fetcher.source_url().unwrap_or_else(|| pkg.name.to_owned()),
+        };
+
+        Self {
+            version,
+            kind: pkg.metadata.kind,
+        }
+    }
+}
+
+impl fmt::Display for PackageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.version, self.kind)
+    }
+}
+
+/// # Package Change
+///
+/// A package which is found under the same key in both lockfiles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageChange {
+    /// The package in the old lockfile
+    pub old: PackageSummary,
+
+    /// The package in the new lockfile
+    pub new: PackageSummary,
+}
+
+/// # Lockfile Diff
+///
+/// A summary of how the packages of a lockfile changed, where
+/// packages are matched by their key in the lockfile
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{Lockfile, lockfile::LockfileDiff};
+///
+/// let old: Lockfile = r#"
+/// {
+///   "lockfileVersion": 1,
+///   "packages": {
+///     "is-number": ["is-number@6.0.0", "", {}, "sha512-Wu1VHeILBK8KAWJUAiSZQX94GmOE45Rg6/538fKwiloUu21KncEkYGPqob2oSZ5mUT73vLGrHQjKw3KMPwfDzg=="],
+///   }
+/// }
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// let new: Lockfile = r#"
+/// {
+///   "lockfileVersion": 1,
+///   "packages": {
+///     "is-number": ["is-number@7.0.0", "", {}, "sha512-41Cifkg6e8TylSpdtTpeLVMqvSBEVzTttHvERD741+pnZ8ANv0004MRL43QKPDlK9cGvNp6NZWZUBlbGXYxxng=="],
+///     "zod": ["zod@github:colinhacks/zod#a5b2bbd", {}, "colinhacks-zod-a5b2bbd"],
+///   }
+/// }
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// let diff = LockfileDiff::new(&old, &new);
+///
+/// assert_eq!(diff.updated["is-number"].new.version, "7.0.0");
+/// assert!(diff.added.contains_key("zod"));
+/// assert_eq!(diff.new_sources, vec!["github:colinhacks/zod#a5b2bbd"]);
+/// ```
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockfileDiff {
+    /// Packages only in the new lockfile
+    pub added: BTreeMap<String, PackageSummary>,
+
+    /// Packages only in the old lockfile
+    pub removed: BTreeMap<String, PackageSummary>,
+
+    /// Packages whose version changed, but are still the same kind of dependency
+    pub updated: BTreeMap<String, PackageChange>,
+
+    /// Packages which changed to a different kind of dependency
+    pub kind_changed: BTreeMap<String, PackageChange>,
+
+    /// Sources in the new lockfile which aren't in the old one,
+    /// and must be prefetched as the lockfile has no hash for them
+    pub new_sources: Vec<String>,
+}
+
+impl LockfileDiff {
+    /// # New Lockfile Diff
+    ///
+    /// Compare the packages of two lockfiles
+    pub fn new(old: &Lockfile, new: &Lockfile) -> Self {
+        let old_packages: BTreeMap<&str, &Package> = old
+            .packages
+            .iter()
+            .map(|pkg| (pkg.key.as_str(), pkg))
+            .collect();
+        let new_packages: BTreeMap<&str, &Package> = new
+            .packages
+            .iter()
+            .map(|pkg| (pkg.key.as_str(), pkg))
+            .collect();
+
+        let mut diff = Self::default();
+
+        for (key, old_pkg) in &old_packages {
+            if !new_packages.contains_key(key) {
+                diff.removed
+                    .insert(key.to_string(), PackageSummary::new(old_pkg));
+            }
+        }
+
+        for (key, new_pkg) in &new_packages {
+            let Some(old_pkg) = old_packages.get(key) else {
+                diff.added
+                    .insert(key.to_string(), PackageSummary::new(new_pkg));
+                continue;
+            };
+
+            let change = PackageChange {
+                old: PackageSummary::new(old_pkg),
+                new: PackageSummary::new(new_pkg),
+            };
+
+            if change.old.kind != change.new.kind {
+                diff.kind_changed.insert(key.to_string(), change);
+            } else if change.old != change.new {
+                diff.updated.insert(key.to_string(), change);
+            }
+        }
+
+        let old_sources: BTreeSet<String> = old
+            .packages
+            .iter()
+            .filter_map(|pkg| pkg.fetcher.source_url())
+            .collect();
+
+        diff.new_sources = new
+            .packages
+            .iter()
+            .filter(|pkg| pkg.fetcher.needs_prefetch())
+            .filter_map(|pkg| pkg.fetcher.source_url())
+            .filter(|source| !old_sources.contains(source))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        diff
+    }
+
+    /// # Is Empty
+    ///
+    /// Whether the packages of the lockfiles are the same
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.updated.is_empty()
+            && self.kind_changed.is_empty()
+    }
+}
+
+/// Renders the diff as a table, with a section for each kind of change
+impl fmt::Display for LockfileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No packages changed");
+        }
+
+        let width = [&self.added, &self.removed]
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .chain(self.updated.keys())
+            .chain(self.kind_changed.keys())
+            .map(String::len)
+            .max()
+            .unwrap_or_default();
+
+        let mut sections = Vec::new();
+
+        for (title, packages) in [("Added", &self.added), ("Removed", &self.removed)] {
+            let rows: Vec<_> = packages
+                .iter()
+                .map(|(key, pkg)| format!("  {:width$}  {}", key, pkg))
+                .collect();
+
+            sections.push((title, rows));
+        }
+
+        for (title, changes) in [
+            ("Updated", &self.updated),
+            ("Changed kind", &self.kind_changed),
+        ] {
+            let rows: Vec<_> = changes
+                .iter()
+                .map(|(key, change)| format!("  {:width$}  {} -> {}", key, change.old, change.new))
+                .collect();
+
+            sections.push((title, rows));
+        }
+
+        sections.push((
+            "New sources to prefetch",
+            self.new_sources
+                .iter()
+                .map(|source| format!("  {}", source))
+                .collect(),
+        ));
+
+        let sections: Vec<String> = sections
+            .into_iter()
+            .filter(|(_, rows)| !rows.is_empty())
+            .map(|(title, rows)| format!("{} ({})\n{}\n", title, rows.len(), rows.join("\n")))
+            .collect();
+
+        write!(f, "{}", sections.join("\n"))
+    }
+}
@@ -1,12 +1,14 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::{Dependency, DependencyKind};
 use crate::{
     Package,
     error::{Error, Result},
-    package::Fetcher,
+    package::{Fetcher, PackageKind, PackageMetadata},
 };
 
-mod prefetch;
-pub use prefetch::Prefetch;
-
 type Values = Vec<serde_json::Value>;
 
 /// # Package Deserializer
@@ -28,15 +30,88 @@ impl PackageDeserializer {
     /// Deserialize a given package from it's lockfile representation
     pub fn deserialize_package(name: String, values: Values) -> Result<Package> {
         let arity = values.len();
+        let mut metadata = Self::deserialize_metadata(&values)?;
+        let dependencies = Self::deserialize_dependencies(&values)?;
+        let key = name.clone();
         let deserializer = Self { name, values };
 
-        match arity {
+        let package = match arity {
             1 => deserializer.deserialize_workspace_package(),
             2 => deserializer.deserialize_tarball_or_file_package(),
             3 => deserializer.deserialize_git_or_github_package(),
             4 => deserializer.deserialize_npm_package(),
             x => Err(Error::UnexpectedPackageEntryLength(x)),
+        }?;
+
+        metadata.kind = PackageKind::new(&package.fetcher, arity == 1);
+
+        Ok(package
+            .with_key(key)
+            .with_metadata(metadata)
+            .with_dependencies(dependencies))
+    }
+
+    /// # Deserialize Dependencies
+    ///
+    /// Deserialize the dependencies of a package from the information
+    /// object in it's lockfile representation, if it has one
+    pub fn deserialize_dependencies(values: &Values) -> Result<Vec<Dependency>> {
+        #[derive(Default, Deserialize)]
+        #[serde(rename_all = "camelCase", default)]
+        struct Info {
+            dependencies: HashMap<String, serde_json::Value>,
+            optional_dependencies: HashMap<String, serde_json::Value>,
+            peer_dependencies: HashMap<String, serde_json::Value>,
         }
+
+        let info: Info = match values.iter().find(|value| value.is_object()) {
+            Some(info) => serde_json::from_value(info.clone())?,
+            None => Info::default(),
+        };
+
+        Ok([
+            (info.dependencies, DependencyKind::Prod),
+            (info.optional_dependencies, DependencyKind::Optional),
+            (info.peer_dependencies, DependencyKind::Peer),
+        ]
+        .into_iter()
+        .flat_map(|(dependencies, kind)| {
+            dependencies
+                .into_keys()
+                .map(move |name| Dependency::new(name, kind))
+        })
+        .collect())
+    }
+
+    /// # Deserialize Metadata
+    ///
+    /// Deserialize the metadata of a package from the information object
+    /// in it's lockfile representation, if it has one
+    pub fn deserialize_metadata(values: &Values) -> Result<PackageMetadata> {
+        let mut metadata: PackageMetadata = match values.iter().find(|value| value.is_object()) {
+            Some(info) => serde_json::from_value(info.clone())?,
+            None => PackageMetadata::default(),
+        };
+
+        let ident = values
+            .first()
+            .and_then(|value| value.as_str())
+            .unwrap_or_default();
+
+        // Skip the first character, as it may be the `@` of a scope
+        let (package_name, version) = match ident.get(1..).and_then(|rest| rest.find('@')) {
+            Some(at) => (&ident[..=at], Some(&ident[at + 2..])),
+            None => (ident, None),
+        };
+
+        metadata.name_single_bin(package_name);
+
+        // Only npm packages have a version after the `@`, rather than a specifier
+        if values.len() == 4 {
+            metadata.version = version.map(ToOwned::to_owned);
+        }
+
+        Ok(metadata)
     }
 
     /// # Deserialize an NPM Package
@@ -84,9 +159,6 @@ impl PackageDeserializer {
     pub fn deserialize_github_package(id: String) -> Result<Package> {
         let (url, rev) = split_once_owned(id, '#').ok_or(Error::MissingGitRef)?;
 
-        let prefetch_url = format!("{}?ref={}", &url, &rev);
-        let prefetch = Prefetch::prefetch_package(&prefetch_url)?;
-
         let (owner_with_pre, repo) = split_once_owned(url, '/').ok_or(Error::ImproperGithubUrl)?;
         let owner = drop_prefix(owner_with_pre, "github:");
 
@@ -96,7 +168,7 @@ impl PackageDeserializer {
             owner,
             repo,
             rev,
-            hash: prefetch.hash,
+            hash: None,
         };
 
         Ok(Package::new(id_with_ver, fetcher))
@@ -111,15 +183,12 @@ impl PackageDeserializer {
         let git_url = drop_prefix(id, "git+");
         let (url, rev) = split_once_owned(git_url, '#').ok_or(Error::MissingGitRef)?;
 
-        let prefetch_url = format!("git+{}?rev={}", &url, &rev);
-        let prefetch = Prefetch::prefetch_package(&prefetch_url)?;
-
         let id_with_rev = format!("git:{}", &rev);
 
         let fetcher = Fetcher::FetchGit {
             url,
             rev,
-            hash: prefetch.hash,
+            hash: None,
         };
 
         Ok(Package::new(id_with_rev, fetcher))
@@ -158,7 +227,7 @@ impl PackageDeserializer {
 
         let path = Self::drain_after_substring(path, "file:").ok_or(Error::MissingFileSpecifier)?;
 
-        Ok(Package::new(name, Fetcher::CopyToStore { path }))
+        Ok(Package::new(name, Fetcher::new_copy_to_store(&path)?))
     }
 
     /// # Deserialize a tarball package
@@ -169,13 +238,8 @@ impl PackageDeserializer {
     pub fn deserialize_tarball_package(url: String) -> Result<Package> {
         debug_assert!(url.contains("http"), "Expected tarball url to contain http");
 
-        let prefetch = Prefetch::prefetch_package(&url)?;
-
         let name = format!("tarball:{}", url);
-        let fetcher = Fetcher::FetchTarball {
-            url,
-            hash: prefetch.hash,
-        };
+        let fetcher = Fetcher::FetchTarball { url, hash: None };
 
         Ok(Package::new(name, fetcher))
     }
@@ -190,7 +254,7 @@ impl PackageDeserializer {
         let path = Self::drain_after_substring(id, "workspace:")
             .ok_or(Error::MissingWorkspaceSpecifier)?;
 
-        Ok(Package::new(self.name, Fetcher::CopyToStore { path }))
+        Ok(Package::new(self.name, Fetcher::new_copy_to_store(&path)?))
     }
 
     fn drain_after_substring(mut input: String, sub: &str) -> Option<String> {
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Dependency, DependencyKind, Workspace};
+
+/// # Workspace Layout
+///
+/// A description of a single workspace in the lockfile, whose
+/// dependencies are given as the names of the packages they
+/// resolve to in the generated output
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkspaceLayout {
+    /// The name of the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The version of the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// The direct dependencies of the workspace, including
+    /// optional and peer dependencies which were installed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+
+    /// The direct dev dependencies of the workspace
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dev_dependencies: Vec<String>,
+}
+
+impl WorkspaceLayout {
+    /// # New Workspace Layout
+    ///
+    /// Describe a workspace from it's resolved dependencies
+    pub fn new<'a>(
+        workspace: &Workspace,
+        dependencies: impl IntoIterator<Item = &'a Dependency>,
+    ) -> Self {
+        let (dev_dependencies, dependencies): (Vec<_>, Vec<_>) = dependencies
+            .into_iter()
+            .partition(|dependency| dependency.kind == DependencyKind::Dev);
+
+        let names = |dependencies: Vec<&Dependency>| {
+            let mut names: Vec<String> = dependencies
+                .into_iter()
+                .map(|dependency| dependency.name.to_owned())
+                .collect();
+
+            names.sort();
+            names.dedup();
+
+            names
+        };
+
+        Self {
+            name: workspace.name.to_owned(),
+            version: workspace.version.to_owned(),
+            dependencies: names(dependencies),
+            dev_dependencies: names(dev_dependencies),
+        }
+    }
+}
@@ -3,22 +3,41 @@
 
 #![warn(missing_docs)]
 
-use bun2nix::{Options, Result, convert_lockfile_to_nix_expression};
+use bun2nix::{
+    Config, Error, OutputFormat, Result, check_output, convert_lockfile_to_nix_expression,
+    convert_lockfile_to_nix_files, diff_lockfiles, explain_package, export_dependency_graph,
+    lockfile::{DependencyKind, GraphFilter, GraphFormat},
+    package::{Fetcher, UrlRewrite},
+    prefetch::PrefetchBackend,
+};
 use log::error;
 
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Command as Process,
 };
 
-use clap::Parser;
+use clap::{
+    CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, error::ErrorKind,
+    parser::ValueSource,
+};
 use env_logger::Env;
 
 /// Convert Bun (v1.2+) packages to Nix expressions
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
+#[command(
+    after_help = "Flags may also be set in a `bun2nix.toml` file, or under the `\"bun2nix\"` key of `package.json`, in the current directory, and switches it sets can be turned off with their `--no-` form, such as `--no-compact`"
+)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    /// Inspect the lockfile instead of converting it
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The Bun (v1.2+) lockfile to use to produce the Nix expression.
     #[arg(short, long, default_value = "./bun.lock")]
     lock_file: PathBuf,
@@ -28,9 +47,200 @@ pub struct Cli {
     #[arg(short, long)]
     output_file: Option<PathBuf>,
 
+    /// The directory to write the output to, split into a file of
+    /// the packages each workspace uses by itself, a file of the
+    /// packages they share and an index `default.nix`
+    #[arg(long, conflicts_with = "output_file")]
+    output_dir: Option<PathBuf>,
+
+    /// Check the existing output is up to date, rather than writing
+    /// it, printing a diff and failing if it is not
+    #[arg(long)]
+    check: bool,
+
     /// The prefix to use when copying workspace or file packages
     #[arg(short, long, default_value = "./")]
     copy_prefix: String,
+
+    /// The format to write the output in
+    #[arg(short, long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    /// Render npm packages as a single line each, through a
+    /// shared helper function, to keep the output small
+    #[arg(long, overrides_with = "no_compact")]
+    compact: bool,
+
+    /// Render npm packages in full, even if the config sets `compact`
+    #[arg(long, overrides_with = "compact", hide = true)]
+    no_compact: bool,
+
+    /// Emit the lockfile metadata of every package, such as
+    /// it's version, executables and platform constraints
+    #[arg(long, overrides_with = "no_metadata")]
+    metadata: bool,
+
+    /// Leave out the lockfile metadata, even if the config sets `metadata`
+    #[arg(long, overrides_with = "metadata", hide = true)]
+    no_metadata: bool,
+
+    /// Only emit the packages installed for this workspace, given by it's
+    /// path or name, alongside the workspace packages it depends on
+    #[arg(short, long)]
+    workspace: Option<String>,
+
+    /// Only emit the packages needed by the dependencies of workspaces,
+    /// leaving out those only needed by their dev dependencies
+    #[arg(long, overrides_with = "no_production")]
+    production: bool,
+
+    /// Emit every package, even if the config sets `production`
+    #[arg(long, overrides_with = "production", hide = true)]
+    no_production: bool,
+
+    /// Emit the packages only needed by dev dependencies in a
+    /// separate `devPackages` attribute, rather than leaving them out
+    #[arg(long, requires = "production", overrides_with = "no_dev_packages")]
+    dev_packages: bool,
+
+    /// Leave out the packages only needed by dev dependencies,
+    /// even if the config sets `dev-packages`
+    #[arg(long, overrides_with = "dev_packages", hide = true)]
+    no_dev_packages: bool,
+
+    /// Replace a prefix of the urls packages are fetched from, such as
+    /// to point them at a mirror, which may be given multiple times.
+    /// Rewritten GitHub sources are fetched with `builtins.fetchTarball`,
+    /// so the mirror must be reachable when evaluating, not only on builders
+    #[arg(long, num_args = 2, value_names = ["PREFIX", "REPLACEMENT"])]
+    rewrite_url: Vec<String>,
+
+    /// Replace the first match of a regex in the urls packages are fetched
+    /// from, tried after every prefix, which may be given multiple times
+    #[arg(long, num_args = 2, value_names = ["PATTERN", "REPLACEMENT"])]
+    rewrite_url_regex: Vec<String>,
+
+    /// A JSON file of sources to fetch packages from in place of those in the
+    /// lockfile, keyed by the package's identifier, such as `zod@3.24.2`
+    #[arg(long)]
+    fetcher_overrides: Option<PathBuf>,
+
+    /// A template to render the Nix expression with, in
+    /// place of the built in one, written in jinja syntax
+    #[arg(short, long)]
+    template: Option<PathBuf>,
+
+    /// The backend to use to prefetch sources
+    /// the lockfile does not contain a hash for
+    #[arg(short, long, value_enum, default_value_t)]
+    prefetcher: PrefetchBackend,
+
+    /// Never prefetch sources, instead failing with a list of
+    /// every source whose hash is not already known
+    #[arg(long, overrides_with = "no_offline")]
+    offline: bool,
+
+    /// Prefetch sources, even if the config sets `offline`
+    #[arg(long, overrides_with = "offline", hide = true)]
+    no_offline: bool,
+
+    /// Emit `lib.fakeHash` placeholders for every source whose
+    /// hash is not already known, instead of prefetching them
+    #[arg(
+        long,
+        conflicts_with = "offline",
+        overrides_with = "no_placeholder_hashes"
+    )]
+    placeholder_hashes: bool,
+
+    /// Prefetch sources rather than emitting placeholders,
+    /// even if the config sets `placeholder-hashes`
+    #[arg(long, overrides_with = "placeholder_hashes", hide = true)]
+    no_placeholder_hashes: bool,
+
+    /// How many seconds a single prefetch command may
+    /// run before it is killed, or `0` to never time out
+    #[arg(long, default_value_t = 300)]
+    prefetch_timeout: u32,
+
+    /// How many times to retry a failed prefetch
+    #[arg(long, default_value_t = 2)]
+    prefetch_retries: u32,
+}
+
+/// Commands which inspect a lockfile, rather than converting it
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Summarize how the packages of a lockfile changed between two versions of it
+    Diff {
+        /// The old lockfile, or a git revision to read the new lockfile at
+        old: String,
+
+        /// The new lockfile
+        #[arg(default_value = "./bun.lock")]
+        new: PathBuf,
+
+        /// The format to print the summary in
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+    /// Explain why a package is in the lockfile, printing the shortest chain
+    /// of dependencies leading to it through each direct workspace dependency
+    Why {
+        /// The package to explain, as `name` or `name@version`
+        package: String,
+
+        /// The Bun (v1.2+) lockfile to read
+        #[arg(short, long, default_value = "./bun.lock")]
+        lock_file: PathBuf,
+
+        /// The format to print the dependency chains in
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+    /// Export the resolved dependency graph of the lockfile
+    Graph {
+        /// The Bun (v1.2+) lockfile to read
+        #[arg(short, long, default_value = "./bun.lock")]
+        lock_file: PathBuf,
+
+        /// The output file to write to -
+        /// if no file location is provided, print to stdout instead.
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+
+        /// The format to write the graph in
+        #[arg(short, long, value_enum, default_value_t)]
+        format: GraphFormat,
+
+        /// Only export the dependencies of this workspace, given by it's path or name
+        #[arg(short, long)]
+        workspace: Option<String>,
+
+        /// Only export packages up to this many edges away from a workspace
+        #[arg(short, long)]
+        depth: Option<usize>,
+
+        /// The kinds of dependencies to follow
+        #[arg(
+            short,
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "prod,dev,optional,peer"
+        )]
+        kind: Vec<DependencyKind>,
+    },
+}
+
+/// The format to print a report about a lockfile in
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// A table, to be read by people
+    #[default]
+    Table,
+    /// A JSON document, to be read by other tools
+    Json,
 }
 
 fn main() {
@@ -48,23 +258,370 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    if let Some(command) = cli.command {
+        return run_command(command);
+    }
+
+    let config = read_config()?;
+
+    // Flags given on the command line take precedence over the config,
+    // while their default values only apply when the config leaves them unset
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
 
-    let lockfile = fs::read_to_string(&cli.lock_file)?;
+    let lock_file = match config.lock_file {
+        Some(lock_file) if !given("lock_file") => PathBuf::from(lock_file),
+        _ => cli.lock_file,
+    };
 
-    let nix = convert_lockfile_to_nix_expression(
-        lockfile,
-        Options {
-            copy_prefix: cli.copy_prefix,
+    let (output_file, output_dir) = match (cli.output_file, cli.output_dir) {
+        (None, None) => match config.output_file {
+            Some(output_file) => (Some(PathBuf::from(output_file)), None),
+            None => (None, config.output_dir.map(PathBuf::from)),
         },
-    )?;
+        output => output,
+    };
+
+    if cli.check && output_file.is_none() && output_dir.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "`--check` requires an output file or directory to compare against",
+            )
+            .exit();
+    }
+
+    let lockfile = fs::read_to_string(&lock_file)?;
+
+    let existing_files = match (&output_file, &output_dir) {
+        (Some(output_file), _) => fs::read_to_string(output_file)
+            .ok()
+            .map(|existing| BTreeMap::from([(output_file.display().to_string(), existing)]))
+            .unwrap_or_default(),
+        (None, Some(output_dir)) => read_output_dir(output_dir)?,
+        (None, None) => BTreeMap::new(),
+    };
+
+    let existing_output = (!existing_files.is_empty()).then(|| {
+        existing_files
+            .values()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    let mut options = config.options;
+
+    if given("copy_prefix") {
+        options.copy_prefix = cli.copy_prefix;
+    }
+
+    if given("format") {
+        options.format = cli.format;
+    }
+
+    if given("prefetcher") {
+        options.prefetch_backend = cli.prefetcher;
+    }
+
+    if given("prefetch_timeout") {
+        options.prefetch_timeout_secs = cli.prefetch_timeout;
+    }
+
+    if given("prefetch_retries") {
+        options.prefetch_retries = cli.prefetch_retries;
+    }
+
+    // A switch is set by it's flag and unset by it's `--no-` form,
+    // keeping the value from the config if neither is given
+    let switch = |on: bool, off: bool, config: bool| (on || config) && !off;
+
+    options.compact = switch(cli.compact, cli.no_compact, options.compact);
+    options.metadata = switch(cli.metadata, cli.no_metadata, options.metadata);
+    options.production = switch(cli.production, cli.no_production, options.production);
+    options.dev_packages = switch(cli.dev_packages, cli.no_dev_packages, options.dev_packages);
+    options.workspace = cli.workspace.or(options.workspace);
+
+    // Offline mode and placeholder hashes conflict,
+    // so either flag turns the other off in the config
+    options.offline = switch(
+        cli.offline,
+        cli.no_offline || cli.placeholder_hashes,
+        options.offline,
+    );
+    options.placeholder_hashes = switch(
+        cli.placeholder_hashes,
+        cli.no_placeholder_hashes || cli.offline,
+        options.placeholder_hashes,
+    );
+
+    // Rules given on the command line are tried before those in the config
+    let mut url_rewrites: Vec<UrlRewrite> = cli
+        .rewrite_url
+        .chunks(2)
+        .map(|rule| UrlRewrite::new_prefix(rule[0].to_owned(), rule[1].to_owned()))
+        .collect();
+
+    for rule in cli.rewrite_url_regex.chunks(2) {
+        url_rewrites.push(UrlRewrite::new_regex(&rule[0], rule[1].to_owned())?);
+    }
+
+    url_rewrites.append(&mut options.url_rewrites);
+    options.url_rewrites = url_rewrites;
+
+    if let Some(path) = &cli.fetcher_overrides {
+        let overrides: BTreeMap<String, Fetcher> =
+            serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        options.fetcher_overrides.extend(overrides);
+    }
 
-    if let Some(output_file) = cli.output_file {
+    options.template = cli
+        .template
+        .or(config.template.map(PathBuf::from))
+        .map(fs::read_to_string)
+        .transpose()?;
+    options.existing_output = existing_output;
+
+    // Checking never prefetches, as any source whose hash isn't
+    // already known means the existing output is out of date
+    options.offline |= cli.check && !options.placeholder_hashes;
+
+    if let Some(output_dir) = output_dir {
+        let files = convert_lockfile_to_nix_files(lockfile, options)?;
+
+        if cli.check {
+            return check_files(&output_dir.display().to_string(), &existing_files, &files);
+        }
+
+        return write_output_dir(&output_dir, files);
+    }
+
+    let nix = convert_lockfile_to_nix_expression(lockfile, options)?;
+
+    if let Some(output_file) = output_file.as_ref().filter(|_| cli.check) {
+        let path = output_file.display().to_string();
+
+        return check_files(
+            &path,
+            &existing_files,
+            &BTreeMap::from([(path.clone(), nix)]),
+        );
+    }
+
+    if let Some(output_file) = output_file {
         let mut output = File::create(output_file)?;
         write!(output, "{nix}")?;
     } else {
-        println!("{nix}");
+        print!("{nix}");
+    }
+
+    Ok(())
+}
+
+/// Read the project config from `bun2nix.toml`, or failing that from
+/// the `"bun2nix"` key of `package.json`, in the current directory
+fn read_config() -> Result<Config> {
+    if Path::new(Config::FILE_NAME).exists() {
+        return Config::from_toml(&fs::read_to_string(Config::FILE_NAME)?);
+    }
+
+    if Path::new("package.json").exists() {
+        return Config::from_package_json(&fs::read_to_string("package.json")?);
+    }
+
+    Ok(Config::default())
+}
+
+/// Run a command which inspects a lockfile
+fn run_command(command: Command) -> Result<()> {
+    match command {
+        Command::Diff { old, new, format } => {
+            let old = match Path::new(&old).is_file() {
+                true => fs::read_to_string(&old)?,
+                false => read_at_revision(&new, &old)?,
+            };
+
+            let diff = diff_lockfiles(&old, &fs::read_to_string(&new)?)?;
+
+            match format {
+                ReportFormat::Table => print!("{diff}"),
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            }
+        }
+        Command::Why {
+            package,
+            lock_file,
+            format,
+        } => {
+            let paths = explain_package(&fs::read_to_string(lock_file)?, &package)?;
+
+            if let ReportFormat::Json = format {
+                println!("{}", serde_json::to_string_pretty(&paths)?);
+
+                return Ok(());
+            }
+
+            for (name, paths) in paths {
+                match paths.len() {
+                    0 => println!("{name} is not depended on by any workspace"),
+                    count => println!("{name} is depended on by {count} path(s):"),
+                }
+
+                for path in paths {
+                    println!("  {path}");
+                }
+            }
+        }
+        Command::Graph {
+            lock_file,
+            output_file,
+            format,
+            workspace,
+            depth,
+            kind,
+        } => {
+            let filter = GraphFilter {
+                workspace,
+                depth,
+                kinds: kind,
+            };
+
+            let graph = export_dependency_graph(&fs::read_to_string(lock_file)?, filter, format)?;
+
+            match output_file {
+                Some(output_file) => fs::write(output_file, graph)?,
+                None => print!("{graph}"),
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Read the contents a file had at a git revision
+fn read_at_revision(path: &Path, revision: &str) -> Result<String> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+
+    let output = Process::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{revision}:./{file}"))
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Error::ReadGitRevisionFailed {
+            path: path.display().to_string(),
+            revision: revision.to_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+
+    String::from_utf8(output.stdout).map_err(|err| Error::InvalidUtf8String(err.utf8_error()))
+}
+
+/// The first line of every file generated by `bun2nix`
+const GENERATED_HEADER: &str = "# Autogenerated by `bun2nix`";
+
+/// Compare freshly rendered files against the existing ones,
+/// printing a diff and failing if any of them differ
+fn check_files(
+    output: &str,
+    existing: &BTreeMap<String, String>,
+    rendered: &BTreeMap<String, String>,
+) -> Result<()> {
+    let diffs: String = existing
+        .keys()
+        .chain(rendered.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|file| {
+            check_output(
+                file,
+                existing.get(file).map(String::as_str).unwrap_or_default(),
+                rendered.get(file).map(String::as_str).unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    if diffs.is_empty() {
+        return Ok(());
+    }
+
+    print!("{diffs}");
+
+    Err(Error::OutputOutOfDate(output.to_owned()))
+}
+
+/// Read every file previously generated into an output
+/// directory, keyed by their path relative to it
+fn read_output_dir(output_dir: &Path) -> Result<BTreeMap<String, String>> {
+    generated_files(output_dir)?
+        .into_iter()
+        .map(|file| Ok((relative_path(output_dir, &file), fs::read_to_string(&file)?)))
+        .collect()
+}
+
+/// The path of a file relative to a directory, separated by `/`
+fn relative_path(dir: &Path, file: &Path) -> String {
+    file.strip_prefix(dir)
+        .unwrap_or(file)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Write a set of files to an output directory, removing any
+/// previously generated files which are no longer part of it
+fn write_output_dir(output_dir: &Path, files: BTreeMap<String, String>) -> Result<()> {
+    for stale in generated_files(output_dir)? {
+        if !files.contains_key(&relative_path(output_dir, &stale)) {
+            fs::remove_file(stale)?;
+        }
+    }
+
+    for (file, contents) in files {
+        let path = output_dir.join(file);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, contents)?;
+    }
+
+    Ok(())
+}
+
+/// Find every nix file generated by `bun2nix` in a directory, recursively
+fn generated_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            files.extend(generated_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "nix")
+            && fs::read_to_string(&path).is_ok_and(|nix| nix.starts_with(GENERATED_HEADER))
+        {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
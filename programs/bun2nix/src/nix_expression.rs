@@ -1,14 +1,25 @@
 //! This module handles construction of the rendered nix code as the output
 
 mod nix_escaper;
+mod runtime_template;
+mod split_expression;
 
 pub use nix_escaper::NixEscaper;
+pub use runtime_template::RuntimeTemplate;
+pub use split_expression::SplitNixExpression;
 
 use crate::{Options, error::Result};
 use askama::Template;
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+};
 
-use crate::Package;
+use crate::{
+    Package, Provenance,
+    lockfile::WorkspaceLayout,
+    package::{Fetcher, UrlRewrite},
+};
 
 /// # Nix Expression
 ///
@@ -17,23 +28,148 @@ use crate::Package;
 #[template(path = "output.nix_template")]
 pub struct NixExpression {
     packages: Vec<Package>,
+    dev_packages: Vec<Package>,
+    provenance: Option<Provenance>,
+    workspaces: BTreeMap<String, WorkspaceLayout>,
+    has_placeholder_hashes: bool,
+    compact: bool,
+    metadata: bool,
+    npm_registry: String,
 }
 
 impl NixExpression {
+    /// # Schema Version
+    ///
+    /// The version of the schema of the nix expression, which is
+    /// incremented whenever `fetchBunDeps` could not consume the
+    /// output of an older version of `bun2nix`
+    pub const SCHEMA_VERSION: u32 = 1;
+
     /// # New Nix Expression
     ///
     /// Produce a new, ready to render, nix expression from a package list
     pub fn new(packages: Vec<Package>) -> Result<Self> {
-        Ok(Self { packages })
+        let has_placeholder_hashes = packages.iter().any(|pkg| pkg.fetcher.needs_prefetch());
+
+        Ok(Self {
+            packages,
+            dev_packages: Vec::new(),
+            provenance: None,
+            workspaces: BTreeMap::new(),
+            has_placeholder_hashes,
+            compact: false,
+            metadata: false,
+            npm_registry: Fetcher::NPM_REGISTRY.to_owned(),
+        })
+    }
+
+    /// # With Dev Packages
+    ///
+    /// Emit the packages only needed by dev dependencies
+    /// separately, in the `devPackages` attribute
+    pub fn with_dev_packages(mut self, dev_packages: Vec<Package>) -> Self {
+        self.has_placeholder_hashes |= dev_packages.iter().any(|pkg| pkg.fetcher.needs_prefetch());
+        self.dev_packages = dev_packages;
+        self
+    }
+
+    /// # With Provenance
+    ///
+    /// Record the provenance of the expression in it's header,
+    /// and in the `_bun2nix` attribute
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// # With Workspaces
+    ///
+    /// Record the layout of the lockfile's workspaces
+    /// in the `workspaces` attribute
+    pub fn with_workspaces(mut self, workspaces: BTreeMap<String, WorkspaceLayout>) -> Self {
+        self.workspaces = workspaces;
+        self
     }
 
     /// # Render with options
     ///
     /// Renders a `NixExpression` with the supplied config options
-    pub fn render_with_options(self, options: Options) -> Result<String> {
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::{Options, Package, nix_expression::NixExpression, package::Fetcher};
+    ///
+    /// let mut options = Options {
+    ///     compact: true,
+    ///     ..Options::default()
+    /// };
+    /// options.add_url_rewrite(
+    ///     "https://registry.npmjs.org".to_owned(),
+    ///     "https://mirror.example.com/npm".to_owned(),
+    /// );
+    ///
+    /// let hash = "sha512-Wu1VHeILBK8KAWJUAiSZQX94GmOE45Rg6/538fKwiloUu21KncEkYGPqob2oSZ5mUT73vLGrHQjKw3KMPwfDzg==";
+    /// let package = Package::new(
+    ///     "is-number@6.0.0".to_owned(),
+    ///     Fetcher::FetchUrl {
+    ///         url: "https://mirror.example.com/npm/is-number/-/is-number-6.0.0.tgz".to_owned(),
+    ///         hash: hash.to_owned(),
+    ///     },
+    /// );
+    ///
+    /// let nix = NixExpression::new(vec![package])
+    ///     .unwrap()
+    ///     .render_with_options(options)
+    ///     .unwrap();
+    ///
+    /// assert!(nix.contains(r#"registry = "https://mirror.example.com/npm";"#));
+    /// assert!(nix.contains(&format!(r#""is-number@6.0.0" = "{}";"#, hash)));
+    /// ```
+    pub fn render_with_options(mut self, options: Options) -> Result<String> {
+        if let Some(template) = &options.template {
+            return RuntimeTemplate::new(template).render(
+                &self.packages,
+                &self.dev_packages,
+                self.provenance.as_ref(),
+                &self.workspaces,
+                &options,
+            );
+        }
+
+        self.compact = options.compact;
+        self.metadata = options.metadata;
+
+        // The compact helper fetches from the registry as rewritten, so
+        // packages fetched from a mirror can still be written compactly
+        if let Some(registry) =
+            UrlRewrite::rewrite_all(&options.url_rewrites, Fetcher::NPM_REGISTRY)
+        {
+            self.npm_registry = registry;
+        }
+
         let mut values: HashMap<&str, Box<dyn Any>> = HashMap::new();
         values.insert("options", Box::new(options));
 
-        Ok(self.render_with_values(&values)?)
+        let mut nix = self.render_with_values(&values)?;
+
+        // `nixfmt` always ends files with a newline
+        nix.push('\n');
+
+        Ok(nix)
+    }
+
+    /// The hash of an npm package which can be fetched by the compact
+    /// helper, each of which are written as a plain string so that
+    /// the line is never wrapped by `nixfmt`, no matter it's length
+    fn compact_hash<'a>(&self, pkg: &'a Package) -> Option<&'a String> {
+        let Fetcher::FetchUrl { url, hash } = &pkg.fetcher else {
+            return None;
+        };
+
+        let npm_url = Fetcher::to_npm_url(&pkg.name).ok()?;
+        let npm_path = npm_url.strip_prefix(Fetcher::NPM_REGISTRY)?;
+
+        (self.compact && url.strip_prefix(self.npm_registry.as_str()) == Some(npm_path))
+            .then_some(hash)
     }
 }
@@ -5,9 +5,124 @@ use askama::filters::Escaper;
 #[derive(Clone, Copy)]
 /// # Nix Escaper
 ///
-/// Escape nix expressions so that they can be written to a template file
+/// Escape values so that they can be written inside of a double
+/// quoted nix string, or a quoted attribute name, in a template file
+///
+/// Every `$` is escaped, so that a value containing `${` can never
+/// be evaluated as an interpolation, while non-ascii characters are
+/// passed through unchanged as nix strings are utf8
+///
+/// ## Usage
+///```rust
+/// use bun2nix::nix_expression::NixEscaper;
+///
+/// assert_eq!(NixEscaper::escape(r#"say "hi""#), r#"say \"hi\""#);
+/// assert_eq!(NixEscaper::escape(r"C:\path"), r"C:\\path");
+/// assert_eq!(NixEscaper::escape("${builtins.abort 1}"), r"\${builtins.abort 1}");
+/// assert_eq!(NixEscaper::escape("line\nbreak"), r"line\nbreak");
+/// assert_eq!(NixEscaper::escape("ünïcødé-📦"), "ünïcødé-📦");
+/// ```
 pub struct NixEscaper;
 
+impl NixEscaper {
+    /// # Escape
+    ///
+    /// Escape a value to be placed inside a double quoted nix string
+    pub fn escape(string: &str) -> String {
+        let mut escaped = String::with_capacity(string.len());
+
+        Self.write_escaped_str(&mut escaped, string)
+            .expect("Writing to a string cannot fail");
+
+        escaped
+    }
+
+    /// # Escape Path
+    ///
+    /// Produce a nix expression for a path relative to the project
+    /// root, joined onto a prefix which is itself a nix path
+    ///
+    /// A plain path literal is used when the path can be written as one,
+    /// otherwise the path is appended to the prefix as an escaped string
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::nix_expression::NixEscaper;
+    ///
+    /// assert_eq!(NixEscaper::escape_path("./", "packages/app"), "./packages/app");
+    /// assert_eq!(
+    ///     NixEscaper::escape_path("./", "packages/my app#1"),
+    ///     r#"(./. + "/packages/my app#1")"#
+    /// );
+    /// assert_eq!(
+    ///     NixEscaper::escape_path("../", "${x}/ünï"),
+    ///     r#"(../. + "/\${x}/ünï")"#
+    /// );
+    /// ```
+    pub fn escape_path(prefix: &str, path: &str) -> String {
+        let literal = format!("{}{}", prefix, path);
+
+        let is_literal = literal.contains('/')
+            && !literal.ends_with('/')
+            && path.split('/').all(|component| {
+                !component.is_empty()
+                    && component
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "._-+".contains(c))
+            });
+
+        if is_literal {
+            return literal;
+        }
+
+        let base = match prefix.trim_end_matches('/') {
+            "" if prefix.starts_with('/') => "/.".to_owned(),
+            "" => "./.".to_owned(),
+            trimmed => format!("{}/.", trimmed),
+        };
+
+        match path {
+            "" => base,
+            path => format!("({} + \"/{}\")", base, Self::escape(path)),
+        }
+    }
+
+    /// # Unescape
+    ///
+    /// Reverse escaping, turning the contents of a double
+    /// quoted nix string back into the value it represents
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::nix_expression::NixEscaper;
+    ///
+    /// let value = "a \"quoted\" ${value} with a \\ and\na newline";
+    ///
+    /// assert_eq!(NixEscaper::unescape(&NixEscaper::escape(value)), value);
+    /// ```
+    pub fn unescape(string: &str) -> String {
+        let mut unescaped = String::with_capacity(string.len());
+        let mut chars = string.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some('t') => unescaped.push('\t'),
+                Some(escaped) => unescaped.push(escaped),
+                None => unescaped.push(c),
+            }
+        }
+
+        unescaped
+    }
+}
+
 impl Escaper for NixEscaper {
     fn write_escaped_str<W: Write>(&self, mut fmt: W, string: &str) -> fmt::Result {
         for character in string.chars() {
@@ -18,6 +133,14 @@ impl Escaper for NixEscaper {
     }
 
     fn write_escaped_char<W: Write>(&self, mut fmt: W, c: char) -> fmt::Result {
-        fmt.write_char(c)
+        match c {
+            '"' => fmt.write_str("\\\""),
+            '\\' => fmt.write_str("\\\\"),
+            '$' => fmt.write_str("\\$"),
+            '\n' => fmt.write_str("\\n"),
+            '\r' => fmt.write_str("\\r"),
+            '\t' => fmt.write_str("\\t"),
+            c => fmt.write_char(c),
+        }
     }
 }
@@ -0,0 +1,148 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+};
+
+use askama::Template;
+use minijinja::{AutoEscape, Environment, UndefinedBehavior, Value, escape_formatter};
+use serde::Serialize;
+
+use super::NixEscaper;
+use crate::{Options, Package, Provenance, error::Result, lockfile::WorkspaceLayout};
+
+/// # Runtime Template
+///
+/// A user supplied template, written in [minijinja](https://docs.rs/minijinja)'s
+/// dialect of jinja, which is rendered in place of the built in output template
+///
+/// Values are escaped to be placed inside a nix string, in the same way as the
+/// built in templates, unless marked with the `safe` filter
+///
+/// ## Usage
+///```rust
+/// use bun2nix::{Options, Package, nix_expression::RuntimeTemplate, package::Fetcher};
+///
+/// let packages = vec![Package::new(
+///     "zod@3.24.2".to_owned(),
+///     Fetcher::new_npm_package("zod@3.24.2", "sha512-lY7CDW43ECgW9u1TcT3IoXHflywfVqDYze4waEz812jR/bZ8FHDsl7pFQoSZTz5N+2NqRXs8GBwnAwo3ZNxqhQ==".to_owned()).unwrap(),
+/// )];
+///
+/// let template = RuntimeTemplate::new(
+///     "{% for pkg in packages %}{{ pkg.name }} = {{ pkg.nix|safe }};{% endfor %}",
+/// );
+///
+/// assert!(
+///     template
+///         .render(&packages, &[], None, &Default::default(), &Options::new("./".to_owned()))
+///         .unwrap()
+///         .starts_with("zod@3.24.2 = fetchurl {")
+/// );
+/// ```
+pub struct RuntimeTemplate<'a> {
+    source: &'a str,
+}
+
+/// A package, alongside the rendering of it's fetcher by the built in templates
+#[derive(Serialize)]
+struct TemplatePackage<'a> {
+    #[serde(flatten)]
+    package: &'a Package,
+    nix: String,
+}
+
+/// The options a template is rendered with
+#[derive(Serialize)]
+struct TemplateOptions<'a> {
+    copy_prefix: &'a str,
+    compact: bool,
+    metadata: bool,
+}
+
+/// Everything a template receives, matching the fields of `NixExpression`
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    packages: Vec<TemplatePackage<'a>>,
+    dev_packages: Vec<TemplatePackage<'a>>,
+    provenance: Option<&'a Provenance>,
+    workspaces: &'a BTreeMap<String, WorkspaceLayout>,
+    has_placeholder_hashes: bool,
+    options: TemplateOptions<'a>,
+}
+
+impl<'a> RuntimeTemplate<'a> {
+    /// # New Runtime Template
+    ///
+    /// Wrap the source of a user supplied template
+    pub fn new(source: &'a str) -> Self {
+        Self { source }
+    }
+
+    /// # Render
+    ///
+    /// Render the template with the same packages, provenance
+    /// and workspaces the built in output template receives
+    pub fn render(
+        &self,
+        packages: &[Package],
+        dev_packages: &[Package],
+        provenance: Option<&Provenance>,
+        workspaces: &BTreeMap<String, WorkspaceLayout>,
+        options: &Options,
+    ) -> Result<String> {
+        let mut env = Environment::new();
+
+        env.set_keep_trailing_newline(true);
+        env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+        env.set_auto_escape_callback(|_| AutoEscape::Custom("nix"));
+        env.set_formatter(|out, state, value| match state.auto_escape() {
+            AutoEscape::Custom("nix") if !value.is_safe() => {
+                Ok(out.write_str(&NixEscaper::escape(&value.to_string()))?)
+            }
+            _ => escape_formatter(out, state, value),
+        });
+
+        let copy_prefix = options.copy_prefix.to_owned();
+        env.add_filter("nix_path", move |path: String| {
+            Value::from_safe_string(NixEscaper::escape_path(&copy_prefix, &path))
+        });
+
+        env.add_template("output", self.source)?;
+
+        let mut values: HashMap<&str, Box<dyn Any>> = HashMap::new();
+        values.insert("options", Box::new(options.clone()));
+
+        let context = TemplateContext {
+            has_placeholder_hashes: packages
+                .iter()
+                .chain(dev_packages)
+                .any(|pkg| pkg.fetcher.needs_prefetch()),
+            packages: Self::template_packages(packages, &values)?,
+            dev_packages: Self::template_packages(dev_packages, &values)?,
+            provenance,
+            workspaces,
+            options: TemplateOptions {
+                copy_prefix: &options.copy_prefix,
+                compact: options.compact,
+                metadata: options.metadata,
+            },
+        };
+
+        Ok(env.get_template("output")?.render(context)?)
+    }
+
+    /// Pair each package with the rendering of it's fetcher
+    fn template_packages<'p>(
+        packages: &'p [Package],
+        values: &HashMap<&str, Box<dyn Any>>,
+    ) -> Result<Vec<TemplatePackage<'p>>> {
+        packages
+            .iter()
+            .map(|package| {
+                Ok(TemplatePackage {
+                    package,
+                    nix: package.fetcher.render_with_values(values)?,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, HashMap};
+
+use askama::Template;
+
+use super::{NixEscaper, NixExpression};
+use crate::{
+    Options, Package, Provenance,
+    error::Result,
+    lockfile::{DependencyGraph, WorkspaceLayout},
+    package::Fetcher,
+};
+
+/// # Split Nix Expression
+///
+/// A set of nix files which together hold the packages of a lockfile,
+/// with one file for the packages each workspace uses exclusively, one
+/// for the packages which are shared between workspaces, and an index
+/// `default.nix` which merges them together
+///
+/// The packages of a workspace only move between files when which
+/// workspaces depend on them changes, so unrelated workspaces never
+/// touch each other's files
+pub struct SplitNixExpression {
+    files: BTreeMap<String, Vec<Package>>,
+    dev_packages: Vec<Package>,
+    provenance: Option<Provenance>,
+    workspaces: BTreeMap<String, WorkspaceLayout>,
+}
+
+impl SplitNixExpression {
+    /// # Index File
+    ///
+    /// The file which merges every other file together
+    pub const INDEX_FILE: &str = "default.nix";
+
+    /// # Shared File
+    ///
+    /// The file which holds packages used by more than one workspace,
+    /// or by none, alongside the provenance of the output
+    pub const SHARED_FILE: &str = "shared.nix";
+
+    /// # Root File
+    ///
+    /// The file which holds packages used only by the root workspace
+    pub const ROOT_FILE: &str = "root.nix";
+
+    /// # New Split Nix Expression
+    ///
+    /// Sort a package list into files, by which workspaces depend on them
+    pub fn new(packages: Vec<Package>, graph: &DependencyGraph) -> Result<Self> {
+        let mut owners: HashMap<String, Vec<&str>> = HashMap::new();
+
+        for path in graph.workspaces.keys() {
+            for name in graph.workspace_closure(path, |_| true) {
+                owners.entry(name).or_default().push(path);
+            }
+        }
+
+        let mut files: BTreeMap<String, Vec<Package>> =
+            BTreeMap::from([(Self::SHARED_FILE.to_owned(), Vec::new())]);
+
+        for pkg in &packages {
+            let file = match owners.get(&pkg.name).map(Vec::as_slice) {
+                Some([path]) => Self::workspace_file(path)?,
+                _ => Self::SHARED_FILE.to_owned(),
+            };
+
+            files.entry(file).or_default().push(pkg.clone());
+        }
+
+        Ok(Self {
+            files,
+            dev_packages: Vec::new(),
+            provenance: None,
+            workspaces: BTreeMap::new(),
+        })
+    }
+
+    /// # With Dev Packages
+    ///
+    /// Emit the packages only needed by dev dependencies
+    /// in the `devPackages` attribute of the shared file
+    pub fn with_dev_packages(mut self, dev_packages: Vec<Package>) -> Self {
+        self.dev_packages = dev_packages;
+        self
+    }
+
+    /// # With Provenance
+    ///
+    /// Record the provenance of the output in the shared file
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// # With Workspaces
+    ///
+    /// Record the layout of the lockfile's workspaces in the shared file
+    pub fn with_workspaces(mut self, workspaces: BTreeMap<String, WorkspaceLayout>) -> Self {
+        self.workspaces = workspaces;
+        self
+    }
+
+    /// # Render with options
+    ///
+    /// Renders every file with the supplied config options,
+    /// keyed by their path relative to the output directory
+    pub fn render_with_options(self, options: Options) -> Result<BTreeMap<String, String>> {
+        let mut rendered = BTreeMap::new();
+
+        let index = SplitIndex {
+            files: self.files.keys().cloned().collect(),
+        };
+
+        // `nixfmt` always ends files with a newline
+        rendered.insert(Self::INDEX_FILE.to_owned(), index.render()? + "\n");
+
+        for (file, packages) in self.files {
+            let mut nix = NixExpression::new(packages)?;
+
+            if file == Self::SHARED_FILE {
+                if let Some(provenance) = &self.provenance {
+                    nix = nix.with_provenance(provenance.clone());
+                }
+
+                nix = nix
+                    .with_dev_packages(self.dev_packages.clone())
+                    .with_workspaces(self.workspaces.clone());
+            }
+
+            let options = Options {
+                copy_prefix: Self::nested_copy_prefix(&options.copy_prefix, &file),
+                ..options.clone()
+            };
+
+            rendered.insert(file, nix.render_with_options(options)?);
+        }
+
+        Ok(rendered)
+    }
+
+    /// Paths in nix are relative to the file they are written in,
+    /// so relative copy prefixes must climb out of any subdirectory
+    fn nested_copy_prefix(copy_prefix: &str, file: &str) -> String {
+        let depth = file.matches('/').count();
+
+        if depth == 0 || copy_prefix.starts_with('/') {
+            return copy_prefix.to_owned();
+        }
+
+        format!(
+            "{}{}",
+            "../".repeat(depth),
+            copy_prefix.strip_prefix("./").unwrap_or(copy_prefix)
+        )
+    }
+
+    /// The file holding the packages used only by a given workspace
+    fn workspace_file(path: &str) -> Result<String> {
+        match Fetcher::normalize_project_path(path)?.as_str() {
+            "" => Ok(Self::ROOT_FILE.to_owned()),
+            path => Ok(format!("workspaces/{}.nix", path)),
+        }
+    }
+}
+
+/// The index of a split nix expression, which merges every file together
+#[derive(Template)]
+#[template(path = "split-index.nix_template")]
+struct SplitIndex {
+    files: Vec<String>,
+}
@@ -1,25 +1,201 @@
 //! This module holds everything related to configuring the output of bun2nix
+use std::{collections::BTreeMap, rc::Rc, time::Duration};
+
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    error::Result,
+    package::{Fetcher, UrlRewrite},
+    prefetch::{PrefetchBackend, Prefetcher},
+};
+
+/// # Output Format
+///
+/// The format to write the converted lockfile in
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A nix expression, to be consumed with `fetchBunDeps` or `pkgs.callPackage`
+    #[default]
+    Nix,
+    /// A JSON document, to be consumed with `fetchBunDeps` or `builtins.fromJSON`
+    Json,
+}
+
 /// # Lockfile conversion options
 ///
-/// Config options for generating a bun.nix file
+/// Config options for generating a bun.nix file, which can be read from
+/// a config file, where each is named after it's command line flag
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
 pub struct Options {
     /// The prefix to use when copying workspace or file packages
     pub copy_prefix: String,
+
+    /// The format to write the output in
+    pub format: OutputFormat,
+
+    /// Render npm packages as a single line each, through a shared
+    /// helper function, instead of as a whole `fetchurl` block
+    pub compact: bool,
+
+    /// Emit the lockfile metadata of every package, such as it's
+    /// version, executables and platform constraints
+    pub metadata: bool,
+
+    /// Only emit the packages installed for a single workspace, given by it's
+    /// path or name, alongside the workspace packages it depends on
+    pub workspace: Option<String>,
+
+    /// Only emit the packages needed by the dependencies of workspaces,
+    /// leaving out those only needed by their dev dependencies
+    pub production: bool,
+
+    /// When only emitting production packages, emit those only needed by
+    /// dev dependencies in a separate `devPackages` attribute, rather than
+    /// leaving them out
+    pub dev_packages: bool,
+
+    /// Rules rewriting the urls packages are fetched from, such as to
+    /// point them at a mirror, where the first matching rule is applied
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(skip))]
+    pub url_rewrites: Vec<UrlRewrite>,
+
+    /// Sources to fetch packages from in place of those in the lockfile,
+    /// keyed by the package's identifier, such as `zod@3.24.2`
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(skip))]
+    pub fetcher_overrides: BTreeMap<String, Fetcher>,
+
+    /// The source of a template to render the nix expression
+    /// with, in place of the built in output template
+    #[serde(skip)]
+    pub template: Option<String>,
+
+    /// The built in backend to use to prefetch sources
+    /// the lockfile does not contain a hash for
+    #[serde(rename = "prefetcher")]
+    pub prefetch_backend: PrefetchBackend,
+
+    /// A custom prefetcher, which takes precedence
+    /// over `prefetch_backend` if supplied
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(skip))]
+    #[serde(skip)]
+    pub prefetcher: Option<Rc<dyn Prefetcher>>,
+
+    /// The contents of a previously generated `bun.nix`, whose
+    /// hashes are reused rather than prefetching the sources again
+    #[serde(skip)]
+    pub existing_output: Option<String>,
+
+    /// Never prefetch sources, and instead fail with a list of
+    /// every source whose hash is not already known
+    pub offline: bool,
+
+    /// Instead of prefetching sources, emit `lib.fakeHash` for every
+    /// source whose hash is not already known
+    pub placeholder_hashes: bool,
+
+    /// How many seconds a single prefetch command may run
+    /// before it is killed, or `0` to never time out
+    #[serde(rename = "prefetch-timeout")]
+    pub prefetch_timeout_secs: u32,
+
+    /// How many times to retry a failed prefetch, with
+    /// an exponential backoff between each attempt
+    pub prefetch_retries: u32,
 }
 
-#[cfg(target_arch = "wasm32")]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl Options {
     /// # New Options
     ///
     /// Constructor for `bun2nix` options
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
     pub fn new(copy_prefix: String) -> Self {
-        Self { copy_prefix }
+        Self {
+            copy_prefix,
+            format: OutputFormat::default(),
+            compact: false,
+            metadata: false,
+            workspace: None,
+            production: false,
+            dev_packages: false,
+            url_rewrites: Vec::new(),
+            fetcher_overrides: BTreeMap::new(),
+            template: None,
+            prefetch_backend: PrefetchBackend::default(),
+            prefetcher: None,
+            existing_output: None,
+            offline: false,
+            placeholder_hashes: false,
+            prefetch_timeout_secs: 300,
+            prefetch_retries: 2,
+        }
+    }
+
+    /// # Add Url Rewrite
+    ///
+    /// Add a rule replacing a prefix of the urls packages are fetched from
+    pub fn add_url_rewrite(&mut self, prefix: String, replacement: String) {
+        self.url_rewrites
+            .push(UrlRewrite::new_prefix(prefix, replacement));
+    }
+
+    /// # Add Regex Url Rewrite
+    ///
+    /// Add a rule replacing the first match of a regex
+    /// in the urls packages are fetched from
+    pub fn add_url_rewrite_regex(&mut self, pattern: &str, replacement: String) -> Result<()> {
+        self.url_rewrites
+            .push(UrlRewrite::new_regex(pattern, replacement)?);
+
+        Ok(())
+    }
+
+    /// # Add Fetcher Override
+    ///
+    /// Fetch a package from a different source than the lockfile's,
+    /// given as a fetcher in the same JSON shape as `JsonOutput`
+    pub fn add_fetcher_override(&mut self, name: String, fetcher: &str) -> Result<()> {
+        self.fetcher_overrides
+            .insert(name, serde_json::from_str(fetcher)?);
+
+        Ok(())
+    }
+}
+
+/// Copies workspace and file packages from the project root
+impl Default for Options {
+    fn default() -> Self {
+        Self::new("./".to_owned())
+    }
+}
+
+impl Options {
+    /// # Prefetcher
+    ///
+    /// Get the prefetcher to use for sources the
+    /// lockfile does not contain a hash for
+    pub fn prefetcher(&self) -> Rc<dyn Prefetcher> {
+        match &self.prefetcher {
+            Some(prefetcher) => prefetcher.clone(),
+            None => self.prefetch_backend.prefetcher(self.prefetch_timeout()),
+        }
+    }
+
+    /// # Prefetch Timeout
+    ///
+    /// Get the timeout for a single prefetch command, if any
+    pub fn prefetch_timeout(&self) -> Option<Duration> {
+        match self.prefetch_timeout_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs.into())),
+        }
     }
 }
@@ -5,14 +5,20 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::lockfile::Dependency;
 
 mod fetcher;
+mod metadata;
+mod url_rewrite;
 
 pub use fetcher::Fetcher;
+pub use metadata::{PackageKind, PackageMetadata};
+pub use url_rewrite::UrlRewrite;
 
-#[derive(Debug, Serialize, Clone)]
-#[serde(rename_all = "camelCase", default)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 /// # Package
 ///
 /// An individual package found in a bun lockfile.
@@ -21,8 +27,22 @@ pub struct Package {
     /// statement
     pub name: String,
 
+    /// The key of the package in the lockfile's `packages` map, which
+    /// dependencies are resolved to, rather than it's full identifier
+    #[serde(skip)]
+    pub key: String,
+
     /// The fetch method to use for the package
     pub fetcher: Fetcher,
+
+    /// Information about the package from the lockfile
+    #[serde(default)]
+    pub metadata: PackageMetadata,
+
+    /// The dependencies of the package, by their name
+    /// in it's `package.json` rather than resolved
+    #[serde(skip)]
+    pub dependencies: Vec<Dependency>,
 }
 
 impl Package {
@@ -31,7 +51,48 @@ impl Package {
     /// Creates a given package using it's name
     /// and fetcher information
     pub fn new(name: String, fetcher: Fetcher) -> Self {
-        Self { name, fetcher }
+        Self {
+            key: name.clone(),
+            name,
+            fetcher,
+            metadata: PackageMetadata::default(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// # With Key
+    ///
+    /// Record the key of a package in the lockfile's `packages` map
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// # With Metadata
+    ///
+    /// Attach the lockfile metadata of a package
+    pub fn with_metadata(mut self, metadata: PackageMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// # Override Fetcher
+    ///
+    /// Replace the source of a package, keeping the
+    /// kind recorded in it's metadata in step
+    pub fn override_fetcher(&mut self, fetcher: Fetcher) {
+        let is_workspace = self.metadata.kind == PackageKind::Workspace;
+
+        self.metadata.kind = PackageKind::new(&fetcher, is_workspace);
+        self.fetcher = fetcher;
+    }
+
+    /// # With Dependencies
+    ///
+    /// Attach the dependencies of a package, as declared in the lockfile
+    pub fn with_dependencies(mut self, dependencies: Vec<Dependency>) -> Self {
+        self.dependencies = dependencies;
+        self
     }
 }
 
@@ -8,15 +8,21 @@ use serde::{Deserialize, Serialize};
 use crate::{
     Options,
     error::{Error, Result},
+    nix_expression::NixEscaper,
+    package::UrlRewrite,
 };
 
 #[derive(Template, Debug, Serialize, Deserialize, Clone, Eq, Ord, PartialEq, PartialOrd, Hash)]
+#[serde(tag = "type")]
 /// # Package Fetcher
 ///
 /// Nix-translated fetcher for a given package
+///
+/// Serialized with a `type` tag matching `Fetcher::kind`
 pub enum Fetcher {
     /// A package which must be retrieved with nix's `pkgs.fetchurl`
     #[template(path = "fetchurl.nix_template")]
+    #[serde(rename = "npm")]
     FetchUrl {
         /// The url to fetch the package from
         url: String,
@@ -26,17 +32,20 @@ pub enum Fetcher {
     },
     /// A package which must be retrieved with nix's `pkgs.fetchgit`
     #[template(path = "fetchgit.nix_template")]
+    #[serde(rename = "git")]
     FetchGit {
         /// The url to fetch the package from
         url: String,
         /// The commit ref to fetch
         rev: String,
         /// The hash of the downloaded results
-        /// This must be calculated via nix-prefetch
-        hash: String,
+        /// This must be calculated via a `Prefetcher`, and
+        /// is rendered as a placeholder while unknown
+        hash: Option<String>,
     },
     /// A package which must be retrieved with nix's `pkgs.fetchFromGitHub`
     #[template(path = "fetchgithub.nix_template")]
+    #[serde(rename = "github")]
     FetchGitHub {
         /// The owner of the repo to fetch from
         owner: String,
@@ -45,20 +54,24 @@ pub enum Fetcher {
         /// The git ref to fetch
         rev: String,
         /// The hash of the downloaded results
-        /// This must be calculated via nix-prefetch
-        hash: String,
+        /// This must be calculated via a `Prefetcher`, and
+        /// is rendered as a placeholder while unknown
+        hash: Option<String>,
     },
     /// A package which must be retrieved with nix's `pkgs.fetchtarball`
     #[template(path = "fetchtarball.nix_template")]
+    #[serde(rename = "tarball")]
     FetchTarball {
         /// The url to fetch the package from
         url: String,
         /// The hash of the downloaded results
-        /// This must be calculated via nix-prefetch
-        hash: String,
+        /// This must be calculated via a `Prefetcher`, and
+        /// is rendered as a placeholder while unknown
+        hash: Option<String>,
     },
     /// A package can be a path copied to the store directly
     #[template(path = "copy-to-store.nix_template")]
+    #[serde(rename = "path")]
     CopyToStore {
         /// The path from the root to copy to the store
         path: String,
@@ -66,6 +79,11 @@ pub enum Fetcher {
 }
 
 impl Fetcher {
+    /// # NPM Registry
+    ///
+    /// The base url of the registry npm packages are fetched from
+    pub const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+
     /// # From NPM Package Name
     ///
     /// Initialize a fetcher from an npm identifier and
@@ -76,6 +94,203 @@ impl Fetcher {
         Ok(Self::FetchUrl { url, hash })
     }
 
+    /// # From Project Path
+    ///
+    /// Initialize a fetcher which copies a path, relative to the
+    /// project root, to the store
+    ///
+    /// The path is normalized, and rejected if it would
+    /// point outside of the project root
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::package::Fetcher;
+    ///
+    /// assert_eq!(
+    ///     Fetcher::new_copy_to_store("./packages/app/").unwrap(),
+    ///     Fetcher::CopyToStore { path: "packages/app".to_owned() }
+    /// );
+    ///
+    /// assert!(Fetcher::new_copy_to_store("../outside").is_err());
+    /// assert!(Fetcher::new_copy_to_store("/etc").is_err());
+    /// ```
+    pub fn new_copy_to_store(path: &str) -> Result<Self> {
+        Ok(Self::CopyToStore {
+            path: Self::normalize_project_path(path)?,
+        })
+    }
+
+    /// # Normalize Project Path
+    ///
+    /// Normalize a path relative to the project root, rejecting
+    /// it if it would point outside of the project root
+    pub fn normalize_project_path(path: &str) -> Result<String> {
+        if path.starts_with('/') {
+            return Err(Error::PathOutsideProjectRoot(path.to_owned()));
+        }
+
+        let mut components = Vec::new();
+
+        for component in path.split('/') {
+            match component {
+                "" | "." => (),
+                ".." => {
+                    components
+                        .pop()
+                        .ok_or_else(|| Error::PathOutsideProjectRoot(path.to_owned()))?;
+                }
+                component => components.push(component),
+            }
+        }
+
+        Ok(components.join("/"))
+    }
+
+    /// # Needs Prefetch
+    ///
+    /// Whether the hash for this fetcher is not yet known
+    /// and must be calculated by a `Prefetcher`
+    pub fn needs_prefetch(&self) -> bool {
+        match self {
+            Self::FetchGit { hash, .. }
+            | Self::FetchGitHub { hash, .. }
+            | Self::FetchTarball { hash, .. } => hash.is_none(),
+            Self::FetchUrl { .. } | Self::CopyToStore { .. } => false,
+        }
+    }
+
+    /// # Set Prefetched Hash
+    ///
+    /// Fill in the hash of a fetcher which had to be prefetched,
+    /// does nothing for fetchers whose hash comes from the lockfile
+    pub fn set_prefetched_hash(&mut self, prefetched: String) {
+        match self {
+            Self::FetchGit { hash, .. }
+            | Self::FetchGitHub { hash, .. }
+            | Self::FetchTarball { hash, .. } => *hash = Some(prefetched),
+            Self::FetchUrl { .. } | Self::CopyToStore { .. } => (),
+        }
+    }
+
+    /// # Rewrite Urls
+    ///
+    /// Rewrite the url this fetcher downloads with the first matching rule
+    ///
+    /// GitHub sources are matched by the url of their archive, and become
+    /// tarballs of it if it is rewritten, which unpack to the same hash
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::package::{Fetcher, UrlRewrite};
+    ///
+    /// let mut fetcher = Fetcher::FetchGitHub {
+    ///     owner: "colinhacks".to_owned(),
+    ///     repo: "zod".to_owned(),
+    ///     rev: "a5b2bbd".to_owned(),
+    ///     hash: None,
+    /// };
+    ///
+    /// fetcher.rewrite_urls(&[UrlRewrite::new_prefix(
+    ///     "https://github.com".to_owned(),
+    ///     "https://mirror.example.com/github".to_owned(),
+    /// )]);
+    ///
+    /// assert_eq!(
+    ///     fetcher,
+    ///     Fetcher::FetchTarball {
+    ///         url: "https://mirror.example.com/github/colinhacks/zod/archive/a5b2bbd.tar.gz".to_owned(),
+    ///         hash: None,
+    ///     }
+    /// );
+    /// ```
+    pub fn rewrite_urls(&mut self, rewrites: &[UrlRewrite]) {
+        match self {
+            Self::FetchUrl { url, .. }
+            | Self::FetchGit { url, .. }
+            | Self::FetchTarball { url, .. } => {
+                if let Some(rewritten) = UrlRewrite::rewrite_all(rewrites, url) {
+                    *url = rewritten;
+                }
+            }
+            Self::FetchGitHub {
+                owner,
+                repo,
+                rev,
+                hash,
+            } => {
+                let archive = format!(
+                    "https://github.com/{}/{}/archive/{}.tar.gz",
+                    owner, repo, rev
+                );
+
+                if let Some(url) = UrlRewrite::rewrite_all(rewrites, &archive) {
+                    *self = Self::FetchTarball {
+                        url,
+                        hash: hash.take(),
+                    };
+                }
+            }
+            Self::CopyToStore { .. } => (),
+        }
+    }
+
+    /// # Source Url
+    ///
+    /// The url of the source this fetcher downloads, written in the same
+    /// style as bun's lockfile specifiers
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::package::Fetcher;
+    ///
+    /// let fetcher = Fetcher::FetchGitHub {
+    ///     owner: "colinhacks".to_owned(),
+    ///     repo: "zod".to_owned(),
+    ///     rev: "a5b2bbd".to_owned(),
+    ///     hash: None,
+    /// };
+    ///
+    /// assert_eq!(
+    ///     fetcher.source_url().unwrap(),
+    ///     "github:colinhacks/zod#a5b2bbd"
+    /// );
+    /// ```
+    pub fn source_url(&self) -> Option<String> {
+        match self {
+            Self::FetchUrl { url, .. } | Self::FetchTarball { url, .. } => Some(url.to_owned()),
+            Self::FetchGit { url, rev, .. } => Some(format!("git+{}#{}", url, rev)),
+            Self::FetchGitHub {
+                owner, repo, rev, ..
+            } => Some(format!("github:{}/{}#{}", owner, repo, rev)),
+            Self::CopyToStore { .. } => None,
+        }
+    }
+
+    /// # Kind
+    ///
+    /// A short name for the kind of source this fetcher downloads
+    ///
+    /// ## Usage
+    ///```rust
+    /// use bun2nix::package::Fetcher;
+    ///
+    /// let fetcher = Fetcher::FetchTarball {
+    ///     url: "https://example.com/pkg.tgz".to_owned(),
+    ///     hash: None,
+    /// };
+    ///
+    /// assert_eq!(fetcher.kind(), "tarball");
+    /// ```
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::FetchUrl { .. } => "npm",
+            Self::FetchGit { .. } => "git",
+            Self::FetchGitHub { .. } => "github",
+            Self::FetchTarball { .. } => "tarball",
+            Self::CopyToStore { .. } => "path",
+        }
+    }
+
     /// # NPM url converter
     ///
     /// Produce a url needed to fetch from the npm api from a package
@@ -98,8 +313,11 @@ impl Fetcher {
             };
 
             return Ok(format!(
-                "https://registry.npmjs.org/{}/-/{}-{}.tgz",
-                name, name, ver
+                "{}/{}/-/{}-{}.tgz",
+                Self::NPM_REGISTRY,
+                name,
+                name,
+                ver
             ));
         };
 
@@ -108,8 +326,12 @@ impl Fetcher {
         };
 
         Ok(format!(
-            "https://registry.npmjs.org/{}/{}/-/{}-{}.tgz",
-            user, name, name, ver
+            "{}/{}/{}/-/{}-{}.tgz",
+            Self::NPM_REGISTRY,
+            user,
+            name,
+            name,
+            ver
         ))
     }
 }
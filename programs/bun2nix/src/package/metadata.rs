@@ -0,0 +1,173 @@
+//! This module holds the metadata bun records about each package in it's lockfile
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::Fetcher;
+
+/// # Package Metadata
+///
+/// Information about a package taken from the bun lockfile,
+/// which can be used to decide how it should be built
+///
+/// ## Usage
+///```rust
+/// use bun2nix::package::{PackageKind, PackageMetadata};
+/// use serde_json::json;
+///
+/// let metadata: PackageMetadata = serde_json::from_value(json!({
+///     "bin": { "esbuild": "bin/esbuild" },
+///     "os": "darwin",
+///     "cpu": ["arm64"],
+/// }))
+/// .unwrap();
+///
+/// assert_eq!(metadata.kind, PackageKind::Npm);
+/// assert_eq!(metadata.bin["esbuild"], "bin/esbuild");
+/// assert_eq!(metadata.os, vec!["darwin"]);
+/// assert_eq!(metadata.cpu, vec!["arm64"]);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PackageMetadata {
+    /// What kind of dependency the package is
+    pub kind: PackageKind,
+
+    /// The version of the package, if it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// The executables the package provides, mapped to their path in the package
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "PackageMetadata::deserialize_bin"
+    )]
+    pub bin: BTreeMap<String, String>,
+
+    /// The operating systems the package supports, or all if empty
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "PackageMetadata::deserialize_one_or_many"
+    )]
+    pub os: Vec<String>,
+
+    /// The cpu architectures the package supports, or all if empty
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "PackageMetadata::deserialize_one_or_many"
+    )]
+    pub cpu: Vec<String>,
+}
+
+impl PackageMetadata {
+    /// # Deserialize Bin
+    ///
+    /// Bun writes a package's executables either as a map of names to
+    /// paths, or a single path which is named after the package
+    pub fn deserialize_bin<'de, D>(data: D) -> Result<BTreeMap<String, String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Bin {
+            Single(String),
+            Named(BTreeMap<String, String>),
+        }
+
+        Ok(match Bin::deserialize(data)? {
+            // The name is filled in once the package name is known
+            Bin::Single(path) => BTreeMap::from([(String::new(), path)]),
+            Bin::Named(bin) => bin,
+        })
+    }
+
+    /// # Deserialize One or Many
+    ///
+    /// Bun writes `os` and `cpu` constraints either as
+    /// a single string, or a list of strings
+    pub fn deserialize_one_or_many<'de, D>(data: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        Ok(match OneOrMany::deserialize(data)? {
+            OneOrMany::One(one) if one == "none" => Vec::new(),
+            OneOrMany::One(one) => vec![one],
+            OneOrMany::Many(many) => many,
+        })
+    }
+
+    /// # Name Single Bin
+    ///
+    /// Name an executable given as a single path after
+    /// the package it belongs to, as npm does
+    pub fn name_single_bin(&mut self, package_name: &str) {
+        if let Some(path) = self.bin.remove("") {
+            let bin_name = package_name.rsplit('/').next().unwrap_or(package_name);
+
+            self.bin.insert(bin_name.to_owned(), path);
+        }
+    }
+}
+
+/// # Package Kind
+///
+/// What kind of dependency a package is
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageKind {
+    /// A package from the npm registry
+    #[default]
+    Npm,
+    /// A package from a git repository
+    Git,
+    /// A package from a github repository
+    GitHub,
+    /// A package from a tarball url
+    Tarball,
+    /// A package from a local path
+    File,
+    /// A package which is a workspace of the project
+    Workspace,
+}
+
+impl PackageKind {
+    /// # From Fetcher
+    ///
+    /// Work out the kind of a package from it's fetcher, given whether it
+    /// is a workspace, as workspace and file packages are both copied
+    /// from the project
+    pub fn new(fetcher: &Fetcher, is_workspace: bool) -> Self {
+        match fetcher {
+            Fetcher::FetchUrl { .. } => Self::Npm,
+            Fetcher::FetchGit { .. } => Self::Git,
+            Fetcher::FetchGitHub { .. } => Self::GitHub,
+            Fetcher::FetchTarball { .. } => Self::Tarball,
+            Fetcher::CopyToStore { .. } if is_workspace => Self::Workspace,
+            Fetcher::CopyToStore { .. } => Self::File,
+        }
+    }
+}
+
+impl fmt::Display for PackageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Npm => "npm",
+            Self::Git => "git",
+            Self::GitHub => "github",
+            Self::Tarball => "tarball",
+            Self::File => "file",
+            Self::Workspace => "workspace",
+        };
+
+        f.write_str(kind)
+    }
+}
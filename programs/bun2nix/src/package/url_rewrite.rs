@@ -0,0 +1,137 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// # Url Rewrite
+///
+/// A rule rewriting the urls packages are fetched from, such as to point
+/// them at a mirror, which leaves their hashes unchanged
+///
+/// ## Usage
+///```rust
+/// use bun2nix::package::UrlRewrite;
+///
+/// let rewrites = [
+///     UrlRewrite::new_prefix(
+///         "https://registry.npmjs.org".to_owned(),
+///         "https://mirror.example.com/npm".to_owned(),
+///     ),
+///     UrlRewrite::new_regex(
+///         r"^https://github\.com/([^/]+)/",
+///         "https://mirror.example.com/github/$1/".to_owned(),
+///     )
+///     .unwrap(),
+/// ];
+///
+/// assert_eq!(
+///     UrlRewrite::rewrite_all(&rewrites, "https://registry.npmjs.org/zod/-/zod-3.24.2.tgz").unwrap(),
+///     "https://mirror.example.com/npm/zod/-/zod-3.24.2.tgz"
+/// );
+/// assert_eq!(
+///     UrlRewrite::rewrite_all(&rewrites, "https://github.com/colinhacks/zod/archive/a5b2bbd.tar.gz").unwrap(),
+///     "https://mirror.example.com/github/colinhacks/zod/archive/a5b2bbd.tar.gz"
+/// );
+/// assert!(UrlRewrite::rewrite_all(&rewrites, "https://example.com/pkg.tgz").is_none());
+///
+/// // Prefixes only match up to a boundary of the url, not in the middle of a host
+/// assert!(
+///     UrlRewrite::rewrite_all(&rewrites, "https://registry.npmjs.org.evil.com/zod/-/zod-3.24.2.tgz")
+///         .is_none()
+/// );
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "UrlRewriteRule")]
+pub enum UrlRewrite {
+    /// Replace a prefix of the url
+    Prefix {
+        /// The prefix to match
+        prefix: String,
+        /// What to replace the prefix with
+        replacement: String,
+    },
+    /// Replace the first match of a regex in the url
+    Regex {
+        /// The regex to match
+        regex: Regex,
+        /// What to replace the match with, which may refer
+        /// to capture groups as `$1` or `$name`
+        replacement: String,
+    },
+}
+
+/// A url rewrite rule as written in a config file, as
+/// either `{ prefix, replacement }` or `{ regex, replacement }`
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum UrlRewriteRule {
+    Prefix { prefix: String, replacement: String },
+    Regex { regex: String, replacement: String },
+}
+
+impl TryFrom<UrlRewriteRule> for UrlRewrite {
+    type Error = Error;
+
+    fn try_from(rule: UrlRewriteRule) -> Result<Self> {
+        match rule {
+            UrlRewriteRule::Prefix {
+                prefix,
+                replacement,
+            } => Ok(Self::new_prefix(prefix, replacement)),
+            UrlRewriteRule::Regex { regex, replacement } => Self::new_regex(&regex, replacement),
+        }
+    }
+}
+
+impl UrlRewrite {
+    /// # New Prefix Rewrite
+    ///
+    /// Create a rule replacing a prefix of the url
+    pub fn new_prefix(prefix: String, replacement: String) -> Self {
+        Self::Prefix {
+            prefix,
+            replacement,
+        }
+    }
+
+    /// # New Regex Rewrite
+    ///
+    /// Create a rule replacing the first match of a regex in the url
+    pub fn new_regex(pattern: &str, replacement: String) -> Result<Self> {
+        Ok(Self::Regex {
+            regex: Regex::new(pattern).map_err(Error::InvalidUrlRewrite)?,
+            replacement,
+        })
+    }
+
+    /// # Rewrite
+    ///
+    /// Rewrite a url, if this rule matches it
+    ///
+    /// A prefix only matches at a boundary of the url, so a prefix of
+    /// `https://registry.npmjs.org` leaves `https://registry.npmjs.org.evil.com`
+    /// alone, unless the prefix itself ends with a `/`
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        match self {
+            Self::Prefix {
+                prefix,
+                replacement,
+            } => url
+                .strip_prefix(prefix.as_str())
+                .filter(|rest| {
+                    prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?', '#'])
+                })
+                .map(|rest| format!("{}{}", replacement, rest)),
+            Self::Regex { regex, replacement } => regex
+                .is_match(url)
+                .then(|| regex.replace(url, replacement.as_str()).into_owned()),
+        }
+    }
+
+    /// # Rewrite All
+    ///
+    /// Rewrite a url with the first of a list of rules which matches it
+    pub fn rewrite_all(rewrites: &[Self], url: &str) -> Option<String> {
+        rewrites.iter().find_map(|rewrite| rewrite.rewrite(url))
+    }
+}
@@ -0,0 +1,496 @@
+//! This module holds everything related to prefetching package sources whose hash is not already
+//! known from the bun lockfile, such as git, github and tarball dependencies
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::{
+    fmt::{self, Debug},
+    io::{self, Read, Write},
+    process::{Child, Command, Stdio},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use log::warn;
+use serde::Deserialize;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Package,
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+mod builtin_prefetcher;
+mod fixture_prefetcher;
+#[cfg(target_arch = "wasm32")]
+mod js_callback;
+mod known_hashes;
+mod nar;
+mod nix_flake_prefetcher;
+mod nix_prefetch_git_prefetcher;
+mod nix_prefetch_url_prefetcher;
+
+pub use builtin_prefetcher::BuiltinPrefetcher;
+pub use fixture_prefetcher::FixturePrefetcher;
+#[cfg(target_arch = "wasm32")]
+pub use js_callback::prefetch_packages_with_callback;
+pub use known_hashes::KnownHashes;
+pub use nar::NarNode;
+pub use nix_flake_prefetcher::NixFlakePrefetcher;
+pub use nix_prefetch_git_prefetcher::NixPrefetchGitPrefetcher;
+pub use nix_prefetch_url_prefetcher::NixPrefetchUrlPrefetcher;
+
+/// # Prefetcher
+///
+/// A backend which can calculate the nix hash of a package source
+/// that the bun lockfile doesn't already contain a hash for.
+///
+/// Library users can implement this themselves and pass it in through
+/// `Options::prefetcher` to hash sources in their own way.
+pub trait Prefetcher: Debug {
+    /// # Prefetch
+    ///
+    /// Calculate the SRI hash of the source the given fetcher downloads
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String>;
+
+    /// # Prefetch Command
+    ///
+    /// A shell command which could be run by hand to hash the
+    /// given fetcher's source, if this prefetcher has one
+    fn prefetch_command(&self, _fetcher: &Fetcher) -> Option<String> {
+        None
+    }
+}
+
+/// # Unhashed Source
+///
+/// A package source which would need to be prefetched
+/// because it's hash is not yet known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnhashedSource {
+    /// The name of the package in the output
+    pub name: String,
+    /// The url of the source to be hashed
+    pub source_url: String,
+    /// The command which would be used to hash it
+    pub command: Option<String>,
+}
+
+impl fmt::Display for UnhashedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "- `{}` ({})", self.name, self.source_url)?;
+
+        if let Some(command) = &self.command {
+            write!(f, "\n    {}", command)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// # Prefetch Backend
+///
+/// The built in `Prefetcher` implementations which can be selected
+/// from the command line
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrefetchBackend {
+    /// Hash sources with `nix flake prefetch`, requires the `nix-command` and `flakes` features
+    #[default]
+    NixFlake,
+    /// Hash sources with `nix-prefetch-url --unpack`, does not support plain git sources
+    NixPrefetchUrl,
+    /// Hash sources with `nix-prefetch-git`, does not support tarball sources
+    NixPrefetchGit,
+    /// Hash sources without nix, using `curl` and `git` to download them
+    Builtin,
+}
+
+impl PrefetchBackend {
+    /// # Prefetcher
+    ///
+    /// Produce the `Prefetcher` implementation for this backend,
+    /// killing any prefetch command which runs longer than `timeout`
+    pub fn prefetcher(self, timeout: Option<Duration>) -> Rc<dyn Prefetcher> {
+        match self {
+            Self::NixFlake => Rc::new(NixFlakePrefetcher { timeout }),
+            Self::NixPrefetchUrl => Rc::new(NixPrefetchUrlPrefetcher { timeout }),
+            Self::NixPrefetchGit => Rc::new(NixPrefetchGitPrefetcher { timeout }),
+            Self::Builtin => Rc::new(BuiltinPrefetcher { timeout }),
+        }
+    }
+}
+
+/// # Unhashed Sources
+///
+/// List every package which still needs to be prefetched,
+/// alongside the command the prefetcher would use to hash it
+pub fn unhashed_sources(packages: &[Package], prefetcher: &dyn Prefetcher) -> Vec<UnhashedSource> {
+    packages
+        .iter()
+        .filter(|pkg| pkg.fetcher.needs_prefetch())
+        .map(|pkg| UnhashedSource {
+            name: pkg.name.to_owned(),
+            source_url: pkg.fetcher.source_url().unwrap_or_default(),
+            command: prefetcher.prefetch_command(&pkg.fetcher),
+        })
+        .collect()
+}
+
+/// # Prefetch Packages
+///
+/// Fill in the hash of every package which needs it using the
+/// supplied prefetcher, retrying failed prefetches up to `retries`
+/// times with an exponential backoff
+///
+/// ## Usage
+///```rust
+/// use std::{cell::Cell, time::Duration};
+///
+/// use bun2nix::{
+///     Error, Package, Result,
+///     package::Fetcher,
+///     prefetch::{Prefetcher, prefetch_packages},
+/// };
+///
+/// /// Times out a number of times before succeeding
+/// #[derive(Debug)]
+/// struct Flaky {
+///     failures: Cell<u32>,
+/// }
+///
+/// impl Prefetcher for Flaky {
+///     fn prefetch(&self, _fetcher: &Fetcher) -> Result<String> {
+///         match self.failures.get() {
+///             0 => Ok("sha256-AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()),
+///             failures => {
+///                 self.failures.set(failures - 1);
+///
+///                 Err(Error::PrefetchTimedOut {
+///                     command: "flaky".to_owned(),
+///                     timeout: Duration::from_secs(1),
+///                 })
+///             }
+///         }
+///     }
+/// }
+///
+/// let tarball = Fetcher::FetchTarball {
+///     url: "https://example.com/pkg.tgz".to_owned(),
+///     hash: None,
+/// };
+///
+/// // Succeeds on the last retry
+/// let mut packages = vec![Package::new("pkg@1.0.0".to_owned(), tarball.clone())];
+/// let flaky = Flaky { failures: Cell::new(2) };
+///
+/// prefetch_packages(&mut packages, &flaky, 2).unwrap();
+/// assert!(!packages[0].fetcher.needs_prefetch());
+///
+/// // Gives up once the retries run out
+/// let mut packages = vec![Package::new("pkg@1.0.0".to_owned(), tarball)];
+/// let flaky = Flaky { failures: Cell::new(2) };
+///
+/// assert!(matches!(
+///     prefetch_packages(&mut packages, &flaky, 1),
+///     Err(Error::PrefetchFailed { attempts: 2, .. })
+/// ));
+/// ```
+pub fn prefetch_packages(
+    packages: &mut [Package],
+    prefetcher: &dyn Prefetcher,
+    retries: u32,
+) -> Result<()> {
+    for package in packages
+        .iter_mut()
+        .filter(|pkg| pkg.fetcher.needs_prefetch())
+    {
+        warn!(
+            "
+Hash was not already known for `{}`.
+
+This must be prefetched and hashed by `bun2nix`. While this
+does have some caching if you care about install speed, try
+looking for an alternative install for this package from npm.
+
+See:
+- https://github.com/oven-sh/bun/issues/19519
+
+Disable these warnings with `RUST_LOG=error` or `RUST_LOG=off`
+        ",
+            package.name
+        );
+
+        let hash = prefetch_with_retries(package, prefetcher, retries)?;
+        package.fetcher.set_prefetched_hash(hash);
+    }
+
+    Ok(())
+}
+
+fn prefetch_with_retries(
+    package: &Package,
+    prefetcher: &dyn Prefetcher,
+    retries: u32,
+) -> Result<String> {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let err = match prefetcher.prefetch(&package.fetcher) {
+            Ok(hash) => return Ok(hash),
+            Err(err) => err,
+        };
+
+        let retryable = matches!(
+            err,
+            Error::PrefetchCommandFailed { .. } | Error::PrefetchTimedOut { .. }
+        );
+
+        if !retryable || attempts > retries {
+            return Err(Error::PrefetchFailed {
+                name: package.name.to_owned(),
+                url: package.fetcher.source_url().unwrap_or_default(),
+                attempts,
+                source: Box::new(err),
+            });
+        }
+
+        let backoff = Duration::from_secs(1 << (attempts - 1).min(5));
+
+        warn!(
+            "Attempt {} to prefetch `{}` failed, retrying in {}s: \n{}",
+            attempts,
+            package.name,
+            backoff.as_secs(),
+            err
+        );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::sleep(backoff);
+    }
+}
+
+/// # Warn Placeholder Hashes
+///
+/// Warn about every package which will be given a
+/// placeholder hash instead of being prefetched
+pub fn warn_placeholder_hashes(packages: &[Package]) {
+    for package in packages.iter().filter(|pkg| pkg.fetcher.needs_prefetch()) {
+        warn!(
+            "
+Hash was not already known for `{}`, so a placeholder
+`lib.fakeHash` has been used instead.
+
+Build once with nix and replace it with the hash nix reports,
+or regenerate with a prefetcher to fill it in automatically.
+
+Disable these warnings with `RUST_LOG=error` or `RUST_LOG=off`
+        ",
+            package.name
+        );
+    }
+}
+
+/// # Nix32 to SRI
+///
+/// Convert a sha256 hash in nix's base32 format, as printed by
+/// the older prefetch tools, into an SRI hash
+///
+/// ## Usage
+///```rust
+/// use bun2nix::prefetch::nix32_to_sri;
+///
+/// assert_eq!(
+///     nix32_to_sri("0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73").unwrap(),
+///     "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+/// );
+/// ```
+pub fn nix32_to_sri(nix32: &str) -> Result<String> {
+    const ALPHABET: &[u8] = b"0123456789abcdfghijklmnpqrsvwxyz";
+    const SHA256_LEN: usize = 32;
+
+    if nix32.len() != 52 {
+        return Err(Error::InvalidNixHash(nix32.to_owned()));
+    }
+
+    let mut bytes = [0u8; SHA256_LEN];
+
+    for (n, char) in nix32.bytes().rev().enumerate() {
+        let digit = ALPHABET
+            .iter()
+            .position(|&c| c == char)
+            .ok_or_else(|| Error::InvalidNixHash(nix32.to_owned()))? as u16;
+
+        let bit = n * 5;
+        let (i, j) = (bit / 8, bit % 8);
+        let shifted = digit << j;
+
+        bytes[i] |= shifted as u8;
+
+        match bytes.get_mut(i + 1) {
+            Some(next) => *next |= (shifted >> 8) as u8,
+            None if shifted >> 8 != 0 => return Err(Error::InvalidNixHash(nix32.to_owned())),
+            None => (),
+        }
+    }
+
+    Ok(format!("sha256-{}", STANDARD.encode(bytes)))
+}
+
+/// # Run Prefetch Command
+///
+/// Run a prefetch command to completion, returning it's stdout if it
+/// succeeded, or killing it if it runs for longer than the timeout
+///
+/// ## Usage
+///```rust
+/// use std::{process::Command, time::{Duration, Instant}};
+///
+/// use bun2nix::{Error, prefetch::run_prefetch_command};
+///
+/// assert_eq!(
+///     run_prefetch_command(Command::new("echo").arg("hi"), None).unwrap(),
+///     "hi\n"
+/// );
+///
+/// // The background `sleep` is killed along with the shell which started it
+/// let started = Instant::now();
+/// let result = run_prefetch_command(
+///     Command::new("sh").args(["-c", "sleep 30 & sleep 30"]),
+///     Some(Duration::from_millis(200)),
+/// );
+///
+/// assert!(matches!(result, Err(Error::PrefetchTimedOut { .. })));
+/// assert!(started.elapsed() < Duration::from_secs(10));
+/// ```
+pub fn run_prefetch_command(command: &mut Command, timeout: Option<Duration>) -> Result<String> {
+    let stdout = run_prefetch_command_bytes(command, timeout)?;
+
+    String::from_utf8(stdout).map_err(|err| Error::InvalidUtf8String(err.utf8_error()))
+}
+
+/// Run a prefetch command to completion, returning it's raw stdout if it succeeded
+fn run_prefetch_command_bytes(command: &mut Command, timeout: Option<Duration>) -> Result<Vec<u8>> {
+    run_prefetch_command_with_input(command, None, timeout)
+}
+
+/// Run a prefetch command to completion, writing the input to it's
+/// stdin if there is any, and returning it's raw stdout if it succeeded
+///
+/// The command is run in it's own process group, so that if it runs for
+/// longer than the timeout it is killed along with every process it started
+fn run_prefetch_command_with_input(
+    command: &mut Command,
+    input: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let mut child = command
+        .stdin(match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::FetchingFailed)?;
+
+    let writer = child
+        .stdin
+        .take()
+        .zip(input)
+        .map(|(mut stdin, input)| thread::spawn(move || stdin.write_all(&input)));
+
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let started = Instant::now();
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(Error::FetchingFailed)? {
+            break status;
+        }
+
+        if let Some(timeout) = timeout.filter(|timeout| started.elapsed() >= *timeout) {
+            kill_process_group(&mut child);
+
+            // The killed child no longer reads it's input, so the writer
+            // finishes with a broken pipe rather than outliving the call
+            let _ = join_writer(writer);
+
+            return Err(Error::PrefetchTimedOut {
+                command: format!("{:?}", command),
+                timeout,
+            });
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    let written = join_writer(writer);
+    let stdout = stdout.join().expect("Reading stdout should not panic");
+    let stderr = stderr.join().expect("Reading stderr should not panic");
+
+    written.map_err(Error::FetchingFailed)?;
+
+    if !status.success() {
+        return Err(Error::PrefetchCommandFailed {
+            command: format!("{:?}", command),
+            status,
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        });
+    }
+
+    Ok(stdout)
+}
+
+/// Wait for the thread writing a command's input to finish, ignoring a
+/// broken pipe, which only means the command exited without reading it all
+fn join_writer(writer: Option<thread::JoinHandle<io::Result<()>>>) -> io::Result<()> {
+    match writer.map(|writer| writer.join().expect("Writing to stdin should not panic")) {
+        Some(Err(err)) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Kill a prefetch command along with every process in it's group, such as
+/// the `git` and `nix-store` processes `nix-prefetch-git` starts, which would
+/// otherwise keep running and hold it's pipes open
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` has no memory safety requirements, and the child
+        // has not been waited on yet, so it's process group still exists
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Read a child process pipe to the end on another thread, so
+/// that the child never blocks on a full pipe
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+
+        buf
+    })
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{io::Read, path::Path, process::Command};
+
+#[cfg(not(target_arch = "wasm32"))]
+use flate2::read::GzDecoder;
+#[cfg(not(target_arch = "wasm32"))]
+use tar::{Archive, EntryType};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::{
+    NarNode, run_prefetch_command, run_prefetch_command_bytes, run_prefetch_command_with_input,
+};
+use super::{NixPrefetchGitPrefetcher, NixPrefetchUrlPrefetcher, Prefetcher};
+use crate::{
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+/// # Builtin Prefetcher
+///
+/// Prefetches sources without needing nix installed, by
+/// downloading them with `curl` or `git`, and then serializing
+/// the unpacked tree as a NAR in order to hash it
+///
+/// Tarball and github sources are unpacked in the same way
+/// as `fetchTarball` and `fetchFromGitHub`, while git sources
+/// are read straight from the git object database, so
+/// executable bits and symlinks are hashed correctly on
+/// every platform
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BuiltinPrefetcher {
+    /// How long a single prefetch command may run before it is killed
+    pub timeout: Option<Duration>,
+}
+
+impl Prefetcher for BuiltinPrefetcher {
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String> {
+        #[cfg(target_arch = "wasm32")]
+        return Err(Error::UnsupportedWASMCliAction(
+            fetcher.source_url().unwrap_or_default(),
+        ));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        return Ok(self.fetch_tree(fetcher)?.nar_hash());
+    }
+
+    fn prefetch_command(&self, fetcher: &Fetcher) -> Option<String> {
+        // The builtin hashing has no command of it's own, so suggest
+        // the nix tools which produce the same hashes
+        match fetcher {
+            Fetcher::FetchGit { .. } => {
+                NixPrefetchGitPrefetcher::default().prefetch_command(fetcher)
+            }
+            _ => NixPrefetchUrlPrefetcher::default().prefetch_command(fetcher),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BuiltinPrefetcher {
+    /// # Fetch Tree
+    ///
+    /// Download the source of a fetcher, and read it
+    /// into memory as nix would unpack it
+    pub fn fetch_tree(&self, fetcher: &Fetcher) -> Result<NarNode> {
+        match fetcher {
+            Fetcher::FetchTarball { url, .. } => Self::unpack_tarball(&self.download(url)?),
+            Fetcher::FetchGitHub {
+                owner, repo, rev, ..
+            } => Self::unpack_tarball(&self.download(&format!(
+                "https://github.com/{}/{}/archive/{}.tar.gz",
+                owner, repo, rev
+            ))?),
+            Fetcher::FetchGit { url, rev, .. } => self.read_git_tree(url, rev),
+            Fetcher::FetchUrl { .. } | Fetcher::CopyToStore { .. } => Err(
+                Error::UnsupportedPrefetchSource("builtin", format!("{:?}", fetcher)),
+            ),
+        }
+    }
+
+    /// # Download
+    ///
+    /// Download the contents of a url with `curl`
+    pub fn download(&self, url: &str) -> Result<Vec<u8>> {
+        run_prefetch_command_bytes(
+            Command::new("curl").args(["--fail", "--silent", "--show-error", "--location", url]),
+            self.timeout,
+        )
+    }
+
+    /// # Unpack Tarball
+    ///
+    /// Read a tar archive, optionally gzip compressed, into memory
+    /// and strip it's single top level directory
+    ///
+    /// ## Usage
+    ///```rust
+    /// use base64::{Engine, engine::general_purpose::STANDARD};
+    /// use bun2nix::{Error, prefetch::BuiltinPrefetcher};
+    ///
+    /// // A `source-1.0` directory holding, in archive order, `zeta.txt`, an executable
+    /// // `bin/run`, `Alpha.txt` with only it's group executable bit set, a symlink
+    /// // `link` to `zeta.txt`, `lib/a.js` and a hard link to it at `lib/copy.js`
+    /// let archive = STANDARD.decode(
+    ///     "H4sIAAAAAAACA+3XzYqDMBQF4Kz7FBm6HvNjoouu5lGsCDoVFROhndJ3n+hmmCBCYZLCeL5N3Lk4nJsb009jWb2LhDMSCndyrZfT8c+V71wrQagmEUzGFqP7Jdkn85P/V2WLxF5tkPwzpTby17/zF0IqTihH/sHNoR8IEPT/3HRsnLoXzH8p/f7zVKP/MRzfltxNfajKuqd1g2Gw1/5/tEMdZAFY7n+9df9nfv9TqdD/GIo5dHQe/eesbbpLsPff9v7vfQvpJgChMtiLBP1fzf/MiuTTxM/fhe3vfzrH/I+hug79aOn9ccIlgP6zsh9ufz4Cnp//Kp/3PxFjOO28/wAAAAAAAAAA8L99AxX94foAKAAA",
+    /// )
+    /// .unwrap();
+    ///
+    /// let tree = BuiltinPrefetcher::unpack_tarball(&archive).unwrap();
+    ///
+    /// assert!(tree.get("source-1.0").is_none());
+    /// assert!(tree.get("lib/copy.js").is_some());
+    ///
+    /// // The hash `nix hash path` gives for the unpacked `source-1.0` directory
+    /// assert_eq!(
+    ///     tree.nar_hash(),
+    ///     "sha256-94D4FErpbDklvuP5kusKoQ+TL2o5tIjhON6b+wdO8io="
+    /// );
+    ///
+    /// // A `source-1.0/caf\xe9.txt` file, whose name is not valid utf8
+    /// let archive = STANDARD.decode(
+    ///     "H4sIAAAAAAACA+3NwQmDQABE0S0lDWh2VUw9InoVdAVbtossOSbXIAjvXf7cZlv2dZyqVMfnOMxnnY8c/i0Wfdd9Wnz3d6fUvprwiOEC+5aHtdwHAAAAAAAAAAAAuI83QrO8nwAoAAA=",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(matches!(
+    ///     BuiltinPrefetcher::unpack_tarball(&archive),
+    ///     Err(Error::NonUtf8Path(_))
+    /// ));
+    /// ```
+    pub fn unpack_tarball(bytes: &[u8]) -> Result<NarNode> {
+        let reader: Box<dyn Read + '_> = if bytes.starts_with(&[0x1f, 0x8b]) {
+            Box::new(GzDecoder::new(bytes))
+        } else {
+            Box::new(bytes)
+        };
+
+        let mut archive = Archive::new(reader);
+        let mut tree = NarNode::directory();
+
+        for entry in archive.entries().map_err(Error::FetchingFailed)? {
+            let mut entry = entry.map_err(Error::FetchingFailed)?;
+
+            let path = utf8_path(&entry.path().map_err(Error::FetchingFailed)?)?;
+
+            let link_name = entry
+                .link_name()
+                .map_err(Error::FetchingFailed)?
+                .map(|link| utf8_path(&link))
+                .transpose()?;
+
+            let node = match (entry.header().entry_type(), link_name) {
+                (EntryType::Regular | EntryType::Continuous, _) => {
+                    let executable = entry.header().mode().map_err(Error::FetchingFailed)? & 0o100;
+
+                    let mut contents = Vec::new();
+                    entry
+                        .read_to_end(&mut contents)
+                        .map_err(Error::FetchingFailed)?;
+
+                    NarNode::file(contents, executable != 0)
+                }
+                (EntryType::Directory, _) => NarNode::directory(),
+                (EntryType::Symlink, Some(target)) => NarNode::symlink(target),
+                (EntryType::Link, Some(target)) => tree
+                    .get(&target)
+                    .cloned()
+                    .ok_or(Error::InvalidArchivePath(target))?,
+                _ => continue,
+            };
+
+            tree.insert(&path, node)?;
+        }
+
+        tree.strip_root()
+    }
+
+    /// # Read Git Tree
+    ///
+    /// Fetch a revision of a git repository and read it's tree into memory
+    ///
+    /// Only the revision itself is fetched where the server allows it, which
+    /// also reaches commits no branch or tag points to any more, falling back
+    /// to fetching every ref the server advertises
+    pub fn read_git_tree(&self, url: &str, rev: &str) -> Result<NarNode> {
+        let dir = tempfile::tempdir().map_err(Error::FetchingFailed)?;
+        let git = || {
+            let mut git = Command::new("git");
+            git.arg("-C").arg(dir.path());
+            git
+        };
+
+        run_prefetch_command(git().args(["init", "--quiet", "--bare"]), self.timeout)?;
+
+        let shallow = run_prefetch_command(
+            git().args(["fetch", "--quiet", "--depth", "1", url, rev]),
+            self.timeout,
+        );
+
+        let tree_ish = match shallow {
+            Ok(_) => "FETCH_HEAD",
+            Err(Error::PrefetchCommandFailed { .. }) => {
+                run_prefetch_command(
+                    git().args(["fetch", "--quiet", url, "+refs/*:refs/*"]),
+                    self.timeout,
+                )?;
+
+                rev
+            }
+            Err(err) => return Err(err),
+        };
+
+        let listing = run_prefetch_command(
+            git().args(["ls-tree", "-r", "-z", "--full-tree", tree_ish]),
+            self.timeout,
+        )?;
+
+        let mut entries = Vec::new();
+
+        for line in listing.split_terminator('\0') {
+            let (meta, path) = line
+                .split_once('\t')
+                .ok_or_else(|| Error::FetchingError(line.to_owned()))?;
+
+            let mut meta = meta.split(' ');
+            let (Some(mode), Some(_), Some(object)) = (meta.next(), meta.next(), meta.next())
+            else {
+                return Err(Error::FetchingError(line.to_owned()));
+            };
+
+            if mode == "160000" {
+                return Err(Error::UnsupportedGitSubmodule(path.to_owned()));
+            }
+
+            entries.push((mode, object, path));
+        }
+
+        let objects: String = entries
+            .iter()
+            .map(|(_, object, _)| format!("{}\n", object))
+            .collect();
+
+        let blobs = run_prefetch_command_with_input(
+            git().args(["cat-file", "--batch"]),
+            Some(objects.into_bytes()),
+            self.timeout,
+        )?;
+
+        let mut tree = NarNode::directory();
+        let mut blobs = blobs.as_slice();
+
+        for (mode, _, path) in entries {
+            let header_end = blobs
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .ok_or_else(|| Error::FetchingError(path.to_owned()))?;
+
+            let header = str::from_utf8(&blobs[..header_end]).map_err(Error::InvalidUtf8String)?;
+            let size: usize = header
+                .rsplit(' ')
+                .next()
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(|| Error::FetchingError(header.to_owned()))?;
+
+            let contents = blobs
+                .get(header_end + 1..header_end + 1 + size)
+                .ok_or_else(|| Error::FetchingError(path.to_owned()))?;
+
+            if path == ".gitattributes" || path.ends_with("/.gitattributes") {
+                check_git_attributes(path, contents)?;
+            }
+
+            let node =
+                match mode {
+                    "120000" => NarNode::symlink(str::from_utf8(contents).map_err(|_| {
+                        Error::NonUtf8Path(String::from_utf8_lossy(contents).into())
+                    })?),
+                    "100755" => NarNode::file(contents, true),
+                    _ => NarNode::file(contents, false),
+                };
+
+            tree.insert(path, node)?;
+
+            // Skip the contents and the trailing newline
+            blobs = blobs.get(header_end + size + 2..).unwrap_or_default();
+        }
+
+        Ok(tree)
+    }
+}
+
+/// Fail on a `.gitattributes` file setting attributes which change the
+/// checked out contents, as the tree is hashed from the raw blobs
+#[cfg(not(target_arch = "wasm32"))]
+fn check_git_attributes(path: &str, contents: &[u8]) -> Result<()> {
+    const ATTRIBUTES: [&str; 5] = ["filter", "eol", "working-tree-encoding", "ident", "export-"];
+
+    let contents = String::from_utf8_lossy(contents);
+    let unsupported = contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split_whitespace().skip(1))
+        // Unset attributes, prefixed with `-` or `!`, leave the contents as is
+        .filter(|attribute| !attribute.starts_with(['-', '!']))
+        .any(|attribute| {
+            ATTRIBUTES
+                .iter()
+                .any(|unsupported| attribute.starts_with(unsupported))
+        });
+
+    match unsupported {
+        true => Err(Error::UnsupportedGitAttributes(path.to_owned())),
+        false => Ok(()),
+    }
+}
+
+/// Convert an archive path to a string, failing rather than replacing
+/// any invalid utf8, which would change the hash of the tree
+#[cfg(not(target_arch = "wasm32"))]
+fn utf8_path(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_owned)
+        .ok_or_else(|| Error::NonUtf8Path(path.to_string_lossy().into_owned()))
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use super::Prefetcher;
+use crate::{
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+/// # Fixture Prefetcher
+///
+/// A test double which never touches the network, and instead
+/// reads hashes from a map of `Fetcher::source_url`s to SRI hashes
+///
+/// ## Usage
+///```rust
+/// use std::rc::Rc;
+///
+/// use bun2nix::{Options, convert_lockfile_to_nix_expression, prefetch::FixturePrefetcher};
+///
+/// let lockfile = r#"
+/// {
+///   "lockfileVersion": 1,
+///   "workspaces": {
+///     "": {
+///       "name": "examples",
+///       "dependencies": {
+///         "zod": "github:colinhacks/zod",
+///       },
+///     },
+///   },
+///   "packages": {
+///     "zod": ["zod@github:colinhacks/zod#a5b2bbd", {}, "colinhacks-zod-a5b2bbd"],
+///   }
+/// }
+/// "#;
+///
+/// let prefetcher = FixturePrefetcher::new([(
+///     "github:colinhacks/zod#a5b2bbd".to_owned(),
+///     "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".to_owned(),
+/// )]);
+///
+/// let options = Options {
+///     prefetcher: Some(Rc::new(prefetcher)),
+///     ..Options::new("./".to_owned())
+/// };
+///
+/// let nix = convert_lockfile_to_nix_expression(lockfile.to_owned(), options).unwrap();
+///
+/// assert!(nix.contains(r#"hash = "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";"#));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct FixturePrefetcher {
+    hashes: HashMap<String, String>,
+}
+
+impl FixturePrefetcher {
+    /// # New Fixture Prefetcher
+    ///
+    /// Create a fixture prefetcher from pairs of
+    /// source urls and their hashes
+    pub fn new(hashes: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            hashes: hashes.into_iter().collect(),
+        }
+    }
+}
+
+impl Prefetcher for FixturePrefetcher {
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String> {
+        let source_url = fetcher.source_url().unwrap_or_default();
+
+        self.hashes
+            .get(&source_url)
+            .cloned()
+            .ok_or(Error::MissingFixtureHash(source_url))
+    }
+}
@@ -0,0 +1,61 @@
+use js_sys::{Function, Promise};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{
+    Package,
+    error::{Error, Result},
+};
+
+/// # Prefetch Packages With Callback
+///
+/// Fill in the hash of every package which needs it by calling
+/// a host provided javascript function with the source url and
+/// kind of each source
+///
+/// The callback may return the SRI hash directly, or a promise
+/// which resolves to it
+pub async fn prefetch_packages_with_callback(
+    packages: &mut [Package],
+    callback: &Function,
+) -> Result<()> {
+    for package in packages
+        .iter_mut()
+        .filter(|pkg| pkg.fetcher.needs_prefetch())
+    {
+        let url = package.fetcher.source_url().unwrap_or_default();
+
+        let failed = |message: String| Error::PrefetchCallbackFailed {
+            name: package.name.to_owned(),
+            url: url.to_owned(),
+            message,
+        };
+
+        let returned = callback
+            .call2(
+                &JsValue::NULL,
+                &JsValue::from_str(&url),
+                &JsValue::from_str(package.fetcher.kind()),
+            )
+            .map_err(|err| failed(js_error_message(err)))?;
+
+        // Resolving a plain value gives it straight back, so sync
+        // and async callbacks can be handled in the same way
+        let hash = JsFuture::from(Promise::resolve(&returned))
+            .await
+            .map_err(|err| failed(js_error_message(err)))?
+            .as_string()
+            .ok_or_else(|| failed("The callback did not return a string".to_owned()))?;
+
+        package.fetcher.set_prefetched_hash(hash);
+    }
+
+    Ok(())
+}
+
+fn js_error_message(err: JsValue) -> String {
+    match js_sys::Reflect::get(&err, &JsValue::from_str("message")) {
+        Ok(message) if message.is_string() => message.as_string().unwrap_or_default(),
+        _ => err.as_string().unwrap_or_else(|| format!("{:?}", err)),
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use crate::{JsonOutput, Package, nix_expression::NixEscaper, package::Fetcher};
+
+/// # Known Hashes
+///
+/// Hashes of prefetched sources recovered from a previously
+/// generated `bun.nix`, keyed by `Fetcher::source_url`, so
+/// that they don't have to be prefetched again
+///
+/// ## Usage
+///```rust
+/// use bun2nix::prefetch::KnownHashes;
+///
+/// let existing_output = r#"
+/// {
+///   "github:colinhacks-zod-a5b2bbd" = fetchFromGitHub {
+///     owner = "colinhacks";
+///     repo = "zod";
+///     rev = "a5b2bbd";
+///     hash = "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+///   };
+/// }
+/// "#;
+///
+/// let known_hashes = KnownHashes::from_nix_expression(existing_output);
+///
+/// assert_eq!(
+///     known_hashes.get("github:colinhacks/zod#a5b2bbd").unwrap(),
+///     "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+/// );
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct KnownHashes {
+    hashes: HashMap<String, String>,
+    /// Whether any prefetched source was found, even one without a hash
+    found_sources: bool,
+}
+
+impl KnownHashes {
+    /// # From Existing Output
+    ///
+    /// Recover the hashes of every prefetched source in
+    /// a previous output of `bun2nix`, in either format
+    pub fn from_existing_output(output: &str) -> Self {
+        match output.parse::<JsonOutput>() {
+            Ok(json) => Self::from_packages(&json.packages),
+            Err(_) => Self::from_nix_expression(output),
+        }
+    }
+
+    /// # From Packages
+    ///
+    /// Collect the hashes of every prefetched source in a list of packages
+    pub fn from_packages(packages: &[Package]) -> Self {
+        let sources: Vec<_> = packages
+            .iter()
+            .filter_map(|pkg| match &pkg.fetcher {
+                Fetcher::FetchGit { hash, .. }
+                | Fetcher::FetchGitHub { hash, .. }
+                | Fetcher::FetchTarball { hash, .. } => Some((pkg.fetcher.source_url()?, hash)),
+                Fetcher::FetchUrl { .. } | Fetcher::CopyToStore { .. } => None,
+            })
+            .collect();
+
+        Self {
+            found_sources: !sources.is_empty(),
+            hashes: sources
+                .into_iter()
+                .filter_map(|(source_url, hash)| Some((source_url, hash.to_owned()?)))
+                .collect(),
+        }
+    }
+
+    /// # From Nix Expression
+    ///
+    /// Recover the hashes of every prefetched source
+    /// in a nix expression previously produced by `bun2nix`
+    ///
+    /// Only output of the built-in templates can be read,
+    /// as sources are matched by the lines they render
+    pub fn from_nix_expression(nix: &str) -> Self {
+        let mut hashes = HashMap::new();
+        let mut found_sources = false;
+        let mut block: Option<(&str, HashMap<&str, String>)> = None;
+
+        for line in nix.lines().map(str::trim) {
+            if let Some((kind, mut fields)) = block.take() {
+                if line.starts_with('}') {
+                    if let Some((source_url, hash)) = Self::block_source(kind, &mut fields) {
+                        found_sources = true;
+                        hashes.extend(hash.map(|hash| (source_url, hash)));
+                    }
+                } else {
+                    if let Some((key, value)) = Self::parse_string_field(line) {
+                        fields.insert(key, value);
+                    }
+
+                    block = Some((kind, fields));
+                }
+
+                continue;
+            }
+
+            block = ["fetchgit", "fetchFromGitHub", "builtins.fetchTarball"]
+                .into_iter()
+                .find(|kind| line.ends_with(&format!("= {} {{", kind)))
+                .map(|kind| (kind, HashMap::new()));
+        }
+
+        Self {
+            hashes,
+            found_sources,
+        }
+    }
+
+    /// # Get
+    ///
+    /// Get the known hash of a given source url
+    pub fn get(&self, source_url: &str) -> Option<&String> {
+        self.hashes.get(source_url)
+    }
+
+    /// # Found Sources
+    ///
+    /// Whether any prefetched source was found in the output,
+    /// even if it only had a placeholder in place of it's hash
+    pub fn found_sources(&self) -> bool {
+        self.found_sources
+    }
+
+    /// # Fill Packages
+    ///
+    /// Fill in the hash of any package which needs
+    /// prefetching, but whose hash is already known
+    pub fn fill_packages(&self, packages: &mut [Package]) {
+        for package in packages
+            .iter_mut()
+            .filter(|pkg| pkg.fetcher.needs_prefetch())
+        {
+            let known = package
+                .fetcher
+                .source_url()
+                .and_then(|source_url| self.get(&source_url));
+
+            if let Some(hash) = known {
+                package.fetcher.set_prefetched_hash(hash.to_owned());
+            }
+        }
+    }
+
+    fn block_source(
+        kind: &str,
+        fields: &mut HashMap<&str, String>,
+    ) -> Option<(String, Option<String>)> {
+        let fetcher = match kind {
+            "fetchgit" => Fetcher::FetchGit {
+                url: fields.remove("url")?,
+                rev: fields.remove("rev")?,
+                hash: None,
+            },
+            "fetchFromGitHub" => Fetcher::FetchGitHub {
+                owner: fields.remove("owner")?,
+                repo: fields.remove("repo")?,
+                rev: fields.remove("rev")?,
+                hash: None,
+            },
+            _ => Fetcher::FetchTarball {
+                url: fields.remove("url")?,
+                hash: None,
+            },
+        };
+
+        let hash = fields.remove("hash").or_else(|| fields.remove("sha256"));
+
+        Some((fetcher.source_url()?, hash))
+    }
+
+    fn parse_string_field(line: &str) -> Option<(&str, String)> {
+        let (key, value) = line.split_once(" = ")?;
+        let value = value
+            .strip_suffix(';')?
+            .strip_prefix('"')?
+            .strip_suffix('"')?;
+
+        Some((key, NixEscaper::unescape(value)))
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+/// # Nar Node
+///
+/// An in memory file system tree, which can be serialized
+/// into a nix archive (NAR) in order to calculate the same
+/// recursive hash as `nix hash path` would
+///
+/// ## Usage
+///```rust
+/// use bun2nix::prefetch::NarNode;
+///
+/// let mut tree = NarNode::directory();
+/// tree.insert("package.json", NarNode::file("{}\n", false)).unwrap();
+/// tree.insert("bin/run", NarNode::file("#!/bin/sh\necho hi\n", true)).unwrap();
+/// tree.insert("link", NarNode::symlink("package.json")).unwrap();
+///
+/// assert_eq!(
+///     tree.nar_hash(),
+///     "sha256-hsKN73AMhqpCzRNovRiSoNySN5SakDRq49v36mK6o6Y="
+/// );
+///
+/// assert_eq!(
+///     NarNode::file("hello\n", false).nar_hash(),
+///     "sha256-HDfQGvQL4ugGkd48w99EN3ppmvuxfGjwgJZLL9Bx/BM="
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NarNode {
+    /// A regular file
+    Regular {
+        /// Whether the file has it's executable bit set
+        executable: bool,
+        /// The contents of the file
+        contents: Vec<u8>,
+    },
+    /// A symbolic link
+    Symlink {
+        /// The path the link points to
+        target: String,
+    },
+    /// A directory, with entries ordered by name as nix requires
+    Directory(BTreeMap<String, NarNode>),
+}
+
+impl NarNode {
+    /// # New Directory
+    ///
+    /// Create an empty directory node
+    pub fn directory() -> Self {
+        Self::Directory(BTreeMap::new())
+    }
+
+    /// # New File
+    ///
+    /// Create a regular file node
+    pub fn file(contents: impl Into<Vec<u8>>, executable: bool) -> Self {
+        Self::Regular {
+            executable,
+            contents: contents.into(),
+        }
+    }
+
+    /// # New Symlink
+    ///
+    /// Create a symbolic link node
+    pub fn symlink(target: impl Into<String>) -> Self {
+        Self::Symlink {
+            target: target.into(),
+        }
+    }
+
+    /// # Insert
+    ///
+    /// Insert a node at a `/` separated path relative to this
+    /// directory, creating any missing parent directories
+    pub fn insert(&mut self, path: &str, node: NarNode) -> Result<()> {
+        let components: Vec<&str> = path
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect();
+
+        if components.contains(&"..") {
+            return Err(Error::InvalidArchivePath(path.to_owned()));
+        }
+
+        let Some((name, parents)) = components.split_last() else {
+            if !matches!((&*self, &node), (Self::Directory(_), Self::Directory(_))) {
+                *self = node;
+            }
+
+            return Ok(());
+        };
+
+        let mut dir = self;
+
+        for parent in parents {
+            let Self::Directory(entries) = dir else {
+                return Err(Error::InvalidArchivePath(path.to_owned()));
+            };
+
+            dir = entries
+                .entry(parent.to_string())
+                .or_insert_with(Self::directory);
+        }
+
+        let Self::Directory(entries) = dir else {
+            return Err(Error::InvalidArchivePath(path.to_owned()));
+        };
+
+        match (entries.get(*name), node) {
+            // Directories may be declared after their contents in an archive
+            (Some(Self::Directory(_)), Self::Directory(_)) => (),
+            (_, node) => {
+                entries.insert(name.to_string(), node);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Get
+    ///
+    /// Look up a node at a `/` separated path relative to this directory
+    pub fn get(&self, path: &str) -> Option<&NarNode> {
+        path.split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .try_fold(self, |node, component| match node {
+                Self::Directory(entries) => entries.get(component),
+                _ => None,
+            })
+    }
+
+    /// # Strip Root
+    ///
+    /// Unwrap the single top level entry of an unpacked archive,
+    /// in the same way `fetchTarball` and `fetchzip` do
+    pub fn strip_root(self) -> Result<Self> {
+        match self {
+            Self::Directory(entries) if entries.len() == 1 => Ok(entries
+                .into_values()
+                .next()
+                .expect("Directory has exactly one entry")),
+            _ => Err(Error::UnexpectedArchiveLayout),
+        }
+    }
+
+    /// # Nar Hash
+    ///
+    /// Serialize this node as a NAR, and produce the SRI
+    /// sha256 hash of it
+    pub fn nar_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+
+        write_str(&mut hasher, b"nix-archive-1");
+        self.write_nar(&mut hasher);
+
+        format!("sha256-{}", STANDARD.encode(hasher.finalize()))
+    }
+
+    fn write_nar(&self, hasher: &mut Sha256) {
+        write_str(hasher, b"(");
+        write_str(hasher, b"type");
+
+        match self {
+            Self::Regular {
+                executable,
+                contents,
+            } => {
+                write_str(hasher, b"regular");
+
+                if *executable {
+                    write_str(hasher, b"executable");
+                    write_str(hasher, b"");
+                }
+
+                write_str(hasher, b"contents");
+                write_str(hasher, contents);
+            }
+            Self::Symlink { target } => {
+                write_str(hasher, b"symlink");
+                write_str(hasher, b"target");
+                write_str(hasher, target.as_bytes());
+            }
+            Self::Directory(entries) => {
+                write_str(hasher, b"directory");
+
+                for (name, node) in entries {
+                    write_str(hasher, b"entry");
+                    write_str(hasher, b"(");
+                    write_str(hasher, b"name");
+                    write_str(hasher, name.as_bytes());
+                    write_str(hasher, b"node");
+                    node.write_nar(hasher);
+                    write_str(hasher, b")");
+                }
+            }
+        }
+
+        write_str(hasher, b")");
+    }
+}
+
+/// Write a length prefixed, zero padded, NAR string
+fn write_str(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+
+    let padding = (8 - bytes.len() % 8) % 8;
+    hasher.update(&[0u8; 8][..padding]);
+}
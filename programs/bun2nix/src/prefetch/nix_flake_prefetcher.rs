@@ -0,0 +1,80 @@
+use std::{process::Command, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Prefetcher, run_prefetch_command};
+use crate::{
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+/// # Nix Flake Prefetcher
+///
+/// Prefetches sources with `nix flake prefetch`, this
+/// supports every source kind, but requires the `nix-command`
+/// and `flakes` experimental features
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NixFlakePrefetcher {
+    /// How long a single prefetch command may run before it is killed
+    pub timeout: Option<Duration>,
+}
+
+/// # Package Prefetch
+///
+/// Represents the result of a `nix flake prefetch`
+/// for a given package we don't know the hash for
+#[derive(Debug, Deserialize, Serialize)]
+struct Prefetch {
+    pub hash: String,
+}
+
+impl NixFlakePrefetcher {
+    /// # Flake Reference
+    ///
+    /// Produce the flake reference to prefetch for
+    /// a given fetcher
+    pub fn flake_ref(fetcher: &Fetcher) -> Option<String> {
+        match fetcher {
+            Fetcher::FetchGit { url, rev, .. } => Some(format!("git+{}?rev={}", url, rev)),
+            Fetcher::FetchGitHub {
+                owner, repo, rev, ..
+            } => Some(format!("github:{}/{}?ref={}", owner, repo, rev)),
+            Fetcher::FetchTarball { url, .. } => Some(url.to_owned()),
+            Fetcher::FetchUrl { .. } | Fetcher::CopyToStore { .. } => None,
+        }
+    }
+}
+
+impl Prefetcher for NixFlakePrefetcher {
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String> {
+        let flake_ref = Self::flake_ref(fetcher).ok_or_else(|| {
+            Error::UnsupportedPrefetchSource("nix flake prefetch", format!("{:?}", fetcher))
+        })?;
+
+        #[cfg(target_arch = "wasm32")]
+        return Err(Error::UnsupportedWASMCliAction(flake_ref));
+
+        let stdout = run_prefetch_command(
+            Command::new("nix").args([
+                "--extra-experimental-features",
+                "nix-command flakes",
+                "flake",
+                "prefetch",
+                &flake_ref,
+                "--json",
+            ]),
+            self.timeout,
+        )?;
+
+        let prefetch: Prefetch = serde_json::from_str(&stdout)?;
+
+        Ok(prefetch.hash)
+    }
+
+    fn prefetch_command(&self, fetcher: &Fetcher) -> Option<String> {
+        Some(format!(
+            "nix --extra-experimental-features 'nix-command flakes' flake prefetch '{}' --json",
+            Self::flake_ref(fetcher)?
+        ))
+    }
+}
@@ -0,0 +1,93 @@
+use std::{process::Command, time::Duration};
+
+use serde::Deserialize;
+
+use super::{Prefetcher, nix32_to_sri, run_prefetch_command};
+use crate::{
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+/// # Nix Prefetch Git Prefetcher
+///
+/// Prefetches sources with `nix-prefetch-git`, which only needs the script
+/// and nix's store tools, `nix-hash` and `nix-store`, rather than the
+/// flakes enabled `nix` command
+///
+/// Github sources are cloned over https, and tarball
+/// sources are not supported
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NixPrefetchGitPrefetcher {
+    /// How long a single prefetch command may run before it is killed
+    pub timeout: Option<Duration>,
+}
+
+/// # Git Prefetch
+///
+/// Represents the json output of `nix-prefetch-git`, older
+/// versions only print the nix32 `sha256` field
+#[derive(Debug, Deserialize)]
+struct GitPrefetch {
+    hash: Option<String>,
+    sha256: String,
+}
+
+impl NixPrefetchGitPrefetcher {
+    /// # Clone Url and Rev
+    ///
+    /// Produce the url and revision to clone for
+    /// a given fetcher
+    pub fn clone_url_and_rev(fetcher: &Fetcher) -> Option<(String, &str)> {
+        match fetcher {
+            Fetcher::FetchGit { url, rev, .. } => Some((url.to_owned(), rev)),
+            Fetcher::FetchGitHub {
+                owner, repo, rev, ..
+            } => Some((format!("https://github.com/{}/{}.git", owner, repo), rev)),
+            Fetcher::FetchTarball { .. }
+            | Fetcher::FetchUrl { .. }
+            | Fetcher::CopyToStore { .. } => None,
+        }
+    }
+}
+
+impl Prefetcher for NixPrefetchGitPrefetcher {
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String> {
+        let (url, rev) = Self::clone_url_and_rev(fetcher).ok_or_else(|| {
+            Error::UnsupportedPrefetchSource("nix-prefetch-git", format!("{:?}", fetcher))
+        })?;
+
+        #[cfg(target_arch = "wasm32")]
+        return Err(Error::UnsupportedWASMCliAction(url));
+
+        let mut command = Command::new("nix-prefetch-git");
+        command.args(["--quiet", "--url", &url, "--rev", rev]);
+
+        // `fetchgit` fetches submodules by default, where `fetchFromGitHub` does not
+        if matches!(fetcher, Fetcher::FetchGit { .. }) {
+            command.arg("--fetch-submodules");
+        }
+
+        let stdout = run_prefetch_command(&mut command, self.timeout)?;
+
+        let prefetch: GitPrefetch = serde_json::from_str(&stdout)?;
+
+        match prefetch.hash {
+            Some(hash) => Ok(hash),
+            None => nix32_to_sri(&prefetch.sha256),
+        }
+    }
+
+    fn prefetch_command(&self, fetcher: &Fetcher) -> Option<String> {
+        let (url, rev) = Self::clone_url_and_rev(fetcher)?;
+
+        let submodules = match fetcher {
+            Fetcher::FetchGit { .. } => " --fetch-submodules",
+            _ => "",
+        };
+
+        Some(format!(
+            "nix-prefetch-git --quiet --url '{}' --rev '{}'{}",
+            url, rev, submodules
+        ))
+    }
+}
@@ -0,0 +1,68 @@
+use std::{process::Command, time::Duration};
+
+use super::{Prefetcher, nix32_to_sri, run_prefetch_command};
+use crate::{
+    error::{Error, Result},
+    package::Fetcher,
+};
+
+/// # Nix Prefetch Url Prefetcher
+///
+/// Prefetches sources with `nix-prefetch-url --unpack`, which
+/// works without any experimental features enabled
+///
+/// Github sources are fetched as their archive tarball, and
+/// plain git sources are not supported
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NixPrefetchUrlPrefetcher {
+    /// How long a single prefetch command may run before it is killed
+    pub timeout: Option<Duration>,
+}
+
+impl NixPrefetchUrlPrefetcher {
+    /// # Unpack Url
+    ///
+    /// Produce the url of the archive to unpack and
+    /// hash for a given fetcher
+    pub fn unpack_url(fetcher: &Fetcher) -> Option<String> {
+        match fetcher {
+            Fetcher::FetchGitHub {
+                owner, repo, rev, ..
+            } => Some(format!(
+                "https://github.com/{}/{}/archive/{}.tar.gz",
+                owner, repo, rev
+            )),
+            Fetcher::FetchTarball { url, .. } => Some(url.to_owned()),
+            Fetcher::FetchGit { .. } | Fetcher::FetchUrl { .. } | Fetcher::CopyToStore { .. } => {
+                None
+            }
+        }
+    }
+}
+
+impl Prefetcher for NixPrefetchUrlPrefetcher {
+    fn prefetch(&self, fetcher: &Fetcher) -> Result<String> {
+        let url = Self::unpack_url(fetcher).ok_or_else(|| {
+            Error::UnsupportedPrefetchSource("nix-prefetch-url", format!("{:?}", fetcher))
+        })?;
+
+        #[cfg(target_arch = "wasm32")]
+        return Err(Error::UnsupportedWASMCliAction(url));
+
+        let stdout = run_prefetch_command(
+            Command::new("nix-prefetch-url").args(["--unpack", "--type", "sha256", &url]),
+            self.timeout,
+        )?;
+
+        let nix32 = stdout.lines().last().unwrap_or_default().trim();
+
+        nix32_to_sri(nix32)
+    }
+
+    fn prefetch_command(&self, fetcher: &Fetcher) -> Option<String> {
+        Some(format!(
+            "nix-prefetch-url --unpack --type sha256 '{}'",
+            Self::unpack_url(fetcher)?
+        ))
+    }
+}
@@ -0,0 +1,54 @@
+//! This module holds the provenance recorded in generated output, so that output which has
+//! drifted from it's lockfile, or was produced by an incompatible generator, can be detected
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// # Provenance
+///
+/// Records which version of `bun2nix` produced an output,
+/// the schema version of that output, and the lockfile
+/// it was produced from
+///
+/// ## Usage
+///```rust
+/// use bun2nix::Provenance;
+///
+/// let provenance = Provenance::new("", 1);
+///
+/// assert_eq!(provenance.version, env!("CARGO_PKG_VERSION"));
+/// assert_eq!(
+///     provenance.lockfile_sha256,
+///     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    /// The version of `bun2nix` which produced the output
+    pub version: String,
+    /// The schema version of the output
+    pub schema_version: u32,
+    /// The hex encoded sha256 of the lockfile contents, as
+    /// produced by `builtins.hashFile "sha256"` in nix
+    pub lockfile_sha256: String,
+}
+
+impl Provenance {
+    /// # New Provenance
+    ///
+    /// Record the provenance of an output with the given
+    /// schema version, produced from a lockfile
+    pub fn new(lockfile: &str, schema_version: u32) -> Self {
+        let lockfile_sha256 = Sha256::digest(lockfile.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            schema_version,
+            lockfile_sha256,
+        }
+    }
+}